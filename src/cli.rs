@@ -0,0 +1,124 @@
+use crate::config::{Format, Overrides};
+use crate::models::LayoutMode;
+
+use clap::{Args, Parser, Subcommand};
+
+/// Runs processes side by side and shows their output in the terminal
+#[derive(Parser, Debug)]
+#[command(
+    name = "control-panel",
+    version,
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start the runners and show their output, the default when no command is given
+    Run(RunArgs),
+    /// Validate the config and report every problem that is found
+    Check(ConfigArgs),
+    /// List the groups and runners of the config
+    List {
+        #[command(flatten)]
+        config: ConfigArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Generate a config from the scripts of the project in the working directory
+    Init {
+        /// File the config is written to
+        #[arg(default_value = "./config.yml")]
+        file: String,
+    },
+    /// Print the version
+    Version,
+    /// Print the completion script for a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the JSON Schema of the config for editor completion
+    Schema,
+}
+
+/// Where the config is read from
+#[derive(Args, Debug, Clone)]
+pub struct ConfigArgs {
+    /// Config file in YAML, TOML or JSON, or a Procfile
+    #[arg(short, long, value_name = "FILE", conflicts_with = "config_file")]
+    pub config: Option<String>,
+    /// Config file, the same as --config
+    #[arg(value_name = "CONFIG")]
+    pub config_file: Option<String>,
+    /// Format of the config, taken from the file name when it isn't given
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+    /// Procfile to load instead of a config
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["config", "config_file"])]
+    pub procfile: Option<String>,
+}
+
+impl ConfigArgs {
+    /// The config file and the format it's read with
+    pub fn file(&self) -> (String, Option<Format>) {
+        match (&self.procfile, &self.config, &self.config_file) {
+            (Some(procfile), _, _) => (procfile.clone(), Some(Format::Procfile)),
+            (None, Some(file), _) | (None, None, Some(file)) => (file.clone(), self.format),
+            (None, None, None) => ("./config.yml".into(), self.format),
+        }
+    }
+}
+
+/// Which runners of the config are used
+#[derive(Args, Debug, Clone)]
+pub struct FilterArgs {
+    /// Only use these runners
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    pub only: Vec<String>,
+    /// Leave out these runners
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    pub exclude: Vec<String>,
+    /// Only use the runners of these groups
+    #[arg(long = "group", value_name = "GROUP", value_delimiter = ',')]
+    pub groups: Vec<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+    /// Don't start any runner until it is started by hand
+    #[arg(long)]
+    pub no_auto_start: bool,
+    /// Layout to start with instead of the one in the config
+    #[arg(long, value_enum)]
+    pub layout: Option<LayoutMode>,
+    /// Print the config merged from all of its files instead of running it
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+impl FilterArgs {
+    pub fn overrides(&self, no_auto_start: bool) -> Overrides {
+        Overrides {
+            only: self.only.clone(),
+            exclude: self.exclude.clone(),
+            groups: self.groups.clone(),
+            no_auto_start,
+        }
+    }
+}
+
+impl RunArgs {
+    pub fn overrides(&self) -> Overrides {
+        self.filter.overrides(self.no_auto_start)
+    }
+}
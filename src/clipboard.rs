@@ -0,0 +1,19 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{
+    env,
+    io::{self, stdout, Write},
+};
+
+/// Copies text to the system clipboard with the OSC 52 escape sequence, which the terminal
+/// handles itself so it also works over SSH
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    // tmux only forwards escape sequences to the outer terminal when they are wrapped
+    if env::var_os("TMUX").is_some() {
+        sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+    }
+
+    let mut out = stdout();
+    out.write_all(sequence.as_bytes())?;
+    out.flush()
+}
@@ -0,0 +1,1996 @@
+use crate::keys::Keymap;
+use crate::models::*;
+use crate::theme::Theme;
+
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use yaml_rust2::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+/// A problem in a config file, with the line and column it was found at when they are known
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+    pub file: String,
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl ConfigError {
+    /// Error at a location, in `file` when the location isn't known
+    fn at(location: Option<Location>, file: &str, message: String) -> Self {
+        match location {
+            Some(location) => ConfigError {
+                file: location.file,
+                position: Some(location.position),
+                message,
+            },
+            None => ConfigError {
+                file: file.to_string(),
+                position: None,
+                message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: {}", self.file, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// Syntax of a config file
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Yaml,
+    Toml,
+    Json,
+    /// `name: command` lines as used by foreman and overmind
+    Procfile,
+}
+
+impl Format {
+    /// Format of a file by its extension, YAML for any other extension. Files named `Procfile`
+    /// or `Procfile.<suffix>` are Procfiles
+    pub fn of(file: &Path) -> Self {
+        let name = file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if name == "Procfile" || name.starts_with("Procfile.") {
+            return Format::Procfile;
+        }
+        match file.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("json") => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+/// Selection of runners and settings given on the command line, applied whenever the config
+/// is loaded
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// Runners that are kept, all of them when it is empty
+    pub only: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Groups whose runners are kept, all of them when it is empty
+    pub groups: Vec<String>,
+    pub no_auto_start: bool,
+}
+
+impl Overrides {
+    /// Removes the runners that aren't selected, and the groups left without runners
+    pub fn apply(&self, cfg: &mut Cfg) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        let runner_names: Vec<&str> = cfg
+            .groups
+            .iter()
+            .flat_map(|g| g.runners.iter().map(|r| r.name.as_str()))
+            .collect();
+        for name in self.only.iter().chain(self.exclude.iter()) {
+            if !runner_names.contains(&name.as_str()) {
+                errors.push(format!("there is no runner named `{name}`"));
+            }
+        }
+        let group_names: Vec<String> = cfg
+            .groups
+            .iter()
+            .enumerate()
+            .map(|(index, group)| group.display_name(index))
+            .collect();
+        for name in self.groups.iter() {
+            if !group_names.contains(name) {
+                errors.push(format!("there is no group named `{name}`"));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let groups = std::mem::take(&mut cfg.groups);
+        for (mut group, group_name) in groups.into_iter().zip(group_names) {
+            if !self.groups.is_empty() && !self.groups.contains(&group_name) {
+                continue;
+            }
+            group.runners.retain(|runner| {
+                (self.only.is_empty() || self.only.contains(&runner.name))
+                    && !self.exclude.contains(&runner.name)
+            });
+            if self.no_auto_start {
+                for runner in group.runners.iter_mut() {
+                    runner.auto_start = false;
+                }
+            }
+            if !group.runners.is_empty() {
+                // The name is kept, so the group doesn't get a different default name
+                group.name = Some(group_name);
+                cfg.groups.push(group);
+            }
+        }
+        if cfg.groups.is_empty() {
+            return Err(vec!["no runners are left after filtering".into()]);
+        }
+        Ok(())
+    }
+}
+
+/// A config file with the location of every value in it
+struct Layer {
+    file: PathBuf,
+    value: Value,
+    locations: Locations,
+}
+
+/// The layers of a config merged into one document
+struct Merged {
+    value: Value,
+    locations: Locations,
+    /// Files of the layers in merge order
+    files: Vec<PathBuf>,
+    /// Problems with the settings that are handled while merging, reported together with the
+    /// problems found when the merged config is parsed
+    errors: Vec<ConfigError>,
+}
+
+/// File that overrides the settings of a config file when it exists, `config.local.yml` for
+/// `config.yml`
+pub fn local_override(file: &Path) -> PathBuf {
+    let stem = file
+        .file_stem()
+        .map_or(String::new(), |s| s.to_string_lossy().into_owned());
+    let name = match file.extension() {
+        Some(extension) => format!("{stem}.local.{}", extension.to_string_lossy()),
+        None => format!("{stem}.local"),
+    };
+    file.with_file_name(name)
+}
+
+/// Reads a config file with its includes and local override and merges them into one layer.
+///
+/// The layers are merged in this order, later ones overriding earlier ones:
+/// 1. the files listed in `include:`, in the order they are listed, each preceded by its own
+///    includes
+/// 2. the file itself
+/// 3. its local override, like `config.local.yml`, together with its includes
+///
+/// Mappings are merged key by key and any other value is replaced. Groups are matched by name
+/// and runners by name in any group, runners that don't exist yet are added to the group they
+/// are listed in. A runner is removed with `enabled: false`.
+fn merge_layers(file: &str, format: Option<Format>) -> Result<Merged, Vec<ConfigError>> {
+    let main = Path::new(file);
+    let mut layers = vec![];
+    let mut errors = vec![];
+    if let Err(layer_errors) = read_layers(main, format, &mut vec![], &mut layers) {
+        errors.extend(layer_errors);
+    }
+    let local = local_override(main);
+    if local.exists() {
+        if let Err(layer_errors) = read_layers(&local, format, &mut vec![], &mut layers) {
+            errors.extend(layer_errors);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut merged = Merged {
+        value: Value::Mapping(Mapping::new()),
+        locations: Locations::default(),
+        files: layers.iter().map(|l| l.file.clone()).collect(),
+        errors: vec![],
+    };
+    for layer in layers {
+        merge_value(
+            &mut merged.value,
+            &mut merged.locations,
+            "",
+            layer.value,
+            &layer.locations,
+            "",
+        );
+    }
+
+    let mut errors = remove_disabled(&mut merged, file);
+    errors.extend(interpolate(&mut merged, file, &|name| env::var(name).ok()));
+    errors.extend(resolve_paths(&mut merged, file));
+    merged.errors = errors;
+    Ok(merged)
+}
+
+/// Reads a file after the files it includes, adding them all to `layers` in merge order. The
+/// format is taken from the extension unless it is given, included files always use their
+/// extension. `including` holds the files that are being read to detect include cycles
+fn read_layers(
+    file: &Path,
+    format: Option<Format>,
+    including: &mut Vec<PathBuf>,
+    layers: &mut Vec<Layer>,
+) -> Result<(), Vec<ConfigError>> {
+    let name = file.display().to_string();
+    let error = |position, message| ConfigError {
+        file: name.clone(),
+        position,
+        message,
+    };
+
+    let contents = fs::read_to_string(file)
+        .map_err(|err| vec![error(None, format!("cannot read the config: {err}"))])?;
+    let (mut value, locations) = match format.unwrap_or_else(|| Format::of(file)) {
+        Format::Yaml => {
+            let locations = Locations::parse(&name, &contents).map_err(|err| {
+                let mark = err.marker();
+                vec![error(
+                    Some((mark.line(), mark.col() + 1)),
+                    err.info().to_string(),
+                )]
+            })?;
+            let mut value: Value = serde_yaml::from_str(&contents).map_err(|err| {
+                let position = err.location().map(|l| (l.line(), l.column()));
+                vec![error(position, without_position(err.to_string(), position))]
+            })?;
+            value
+                .apply_merge()
+                .map_err(|err| vec![error(None, err.to_string())])?;
+            (value, locations)
+        }
+        Format::Json => {
+            let value: Value = serde_json::from_str(&contents).map_err(|err| {
+                let position = (err.line(), err.column());
+                vec![error(
+                    Some(position),
+                    without_position(err.to_string(), Some(position)),
+                )]
+            })?;
+            // JSON is also YAML, but files that YAML doesn't accept, e.g. because of tabs, are
+            // still loaded without positions
+            let locations = Locations::parse(&name, &contents).unwrap_or_else(|_| Locations {
+                file: name.clone(),
+                ..Default::default()
+            });
+            (value, locations)
+        }
+        Format::Toml => {
+            let document = toml_edit::Document::parse(contents.as_str()).map_err(|err| {
+                let position = err.span().map(|span| line_column(&contents, span.start));
+                vec![error(position, err.message().to_string())]
+            })?;
+            let value: Value = toml_edit::de::from_str(&contents).map_err(|err| {
+                let position = err.span().map(|span| line_column(&contents, span.start));
+                vec![error(position, err.message().to_string())]
+            })?;
+            let mut locations = Locations {
+                file: name.clone(),
+                ..Default::default()
+            };
+            locations.add_toml_table(&contents, "", document.as_table());
+            (value, locations)
+        }
+        Format::Procfile => read_procfile(file, &name, &contents)?,
+    };
+    if value.is_null() {
+        value = Value::Mapping(Mapping::new());
+    }
+    let Value::Mapping(mapping) = &mut value else {
+        return Err(vec![error(
+            Some((1, 1)),
+            "the config has to be a mapping of settings".into(),
+        )]);
+    };
+
+    let mut errors = vec![];
+    let includes: Vec<(String, String)> = match mapping.remove("include") {
+        None => vec![],
+        Some(Value::String(path)) => vec![(path, "include".into())],
+        Some(Value::Sequence(paths)) => paths
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, path)| {
+                let location_path = format!("include.{index}");
+                match path {
+                    Value::String(path) => Some((path, location_path)),
+                    _ => {
+                        errors.push(error(
+                            locations.find(&location_path).map(|l| l.position),
+                            "an include has to be the path of a file".into(),
+                        ));
+                        None
+                    }
+                }
+            })
+            .collect(),
+        Some(_) => {
+            errors.push(error(
+                locations.find("include").map(|l| l.position),
+                "`include` has to be a path or a list of paths".into(),
+            ));
+            vec![]
+        }
+    };
+
+    including.push(fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf()));
+    for (path, location_path) in includes {
+        let included = file.parent().unwrap_or(Path::new("")).join(&path);
+        let canonical = fs::canonicalize(&included).unwrap_or_else(|_| included.clone());
+        if including.contains(&canonical) {
+            errors.push(error(
+                locations.find(&location_path).map(|l| l.position),
+                format!("`{path}` includes this file again, includes can't form a cycle"),
+            ));
+        } else if let Err(err) = fs::metadata(&included) {
+            errors.push(error(
+                locations.find(&location_path).map(|l| l.position),
+                format!("cannot read the included file `{path}`: {err}"),
+            ));
+        } else if let Err(include_errors) = read_layers(&included, None, including, layers) {
+            errors.extend(include_errors);
+        }
+    }
+    including.pop();
+
+    layers.push(Layer {
+        file: file.to_path_buf(),
+        value,
+        locations,
+    });
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// A process of a Procfile with the environment it runs with
+pub struct ProcfileProcess {
+    pub name: String,
+    /// Run with `sh -c`
+    pub command: String,
+    pub env: Vec<(String, String)>,
+    pub line: usize,
+}
+
+/// Reads the `name: command` lines of a Procfile. The variables of a `.env` file next to it are
+/// set for every process, and each process gets its own `PORT`, 100 apart starting at the `PORT`
+/// of the `.env` file or the environment, or at 5000
+pub fn parse_procfile(
+    file: &Path,
+    contents: &str,
+) -> Result<Vec<ProcfileProcess>, Vec<ConfigError>> {
+    let name = file.display().to_string();
+    let error = |file: &str, position, message| ConfigError {
+        file: file.to_string(),
+        position,
+        message,
+    };
+
+    let mut env_values = vec![];
+    let env_file = file.parent().unwrap_or(Path::new("")).join(".env");
+    if env_file.exists() {
+        let env_name = env_file.display().to_string();
+        let entries = dotenvy::from_path_iter(&env_file)
+            .map_err(|err| vec![error(&env_name, None, err.to_string())])?;
+        for entry in entries {
+            let (key, value) = entry.map_err(|err| match err {
+                dotenvy::Error::LineParse(line, index) => vec![error(
+                    &env_name,
+                    None,
+                    format!("cannot parse `{line}` at character {index}"),
+                )],
+                err => vec![error(&env_name, None, err.to_string())],
+            })?;
+            env_values.retain(|(k, _): &(String, String)| *k != key);
+            env_values.push((key, value));
+        }
+    }
+    let base_port = env_values
+        .iter()
+        .find(|(key, _)| key == "PORT")
+        .map(|(_, port)| port.clone())
+        .or_else(|| env::var("PORT").ok())
+        .and_then(|port| port.parse::<u32>().ok())
+        .unwrap_or(5000);
+    env_values.retain(|(key, _)| key != "PORT");
+
+    let mut processes = vec![];
+    let mut errors = vec![];
+    for (line_index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((process, command)) = line
+            .split_once(':')
+            .map(|(process, command)| (process.trim(), command.trim()))
+            .filter(|(process, command)| !process.is_empty() && !command.is_empty())
+        else {
+            errors.push(error(
+                &name,
+                Some((line_index + 1, 1)),
+                "expected a `name: command` line".into(),
+            ));
+            continue;
+        };
+
+        let mut env = env_values.clone();
+        let port = base_port + 100 * processes.len() as u32;
+        env.push(("PORT".into(), port.to_string()));
+        processes.push(ProcfileProcess {
+            name: process.into(),
+            command: command.into(),
+            env,
+            line: line_index + 1,
+        });
+    }
+    if errors.is_empty() {
+        Ok(processes)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Turns the processes of a Procfile into runners that run the command with `sh -c` in the
+/// Procfile's directory, like foreman does
+fn read_procfile(
+    file: &Path,
+    name: &str,
+    contents: &str,
+) -> Result<(Value, Locations), Vec<ConfigError>> {
+    let mut locations = Locations {
+        file: name.to_string(),
+        ..Default::default()
+    };
+    let mut runners = vec![];
+    for process in parse_procfile(file, contents)? {
+        locations.positions.insert(
+            format!("groups.0.runners.{}", runners.len()),
+            Location {
+                file: name.to_string(),
+                position: (process.line, 1),
+            },
+        );
+        let env: Mapping = process
+            .env
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.replace("${", "$${").into()))
+            .collect();
+
+        let mut runner = Mapping::new();
+        runner.insert("name".into(), process.name.into());
+        runner.insert("dir".into(), ".".into());
+        // The command is expanded by the shell, not by the config
+        let command = process.command.replace("${", "$${");
+        runner.insert("args".into(), vec!["sh", "-c", command.as_str()].into());
+        runner.insert("env".into(), Value::Mapping(env));
+        runners.push(Value::Mapping(runner));
+    }
+
+    let mut group = Mapping::new();
+    group.insert("name".into(), "Procfile".into());
+    group.insert("runners".into(), Value::Sequence(runners));
+    let mut root = Mapping::new();
+    root.insert(
+        "groups".into(),
+        Value::Sequence(vec![Value::Mapping(group)]),
+    );
+    Ok((Value::Mapping(root), locations))
+}
+
+/// Merges `overlay` into `base`, moving the locations of the values that are taken over
+fn merge_value(
+    base: &mut Value,
+    locations: &mut Locations,
+    path: &str,
+    overlay: Value,
+    overlay_locations: &Locations,
+    overlay_path: &str,
+) {
+    match (base, overlay) {
+        (Value::Sequence(base), Value::Sequence(overlay)) if path == "groups" => {
+            merge_groups(base, locations, overlay, overlay_locations);
+        }
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                let name = key.as_str().unwrap_or_default().to_string();
+                let key_path = join(path, &name);
+                let overlay_key_path = join(overlay_path, &name);
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_value(
+                        base_value,
+                        locations,
+                        &key_path,
+                        value,
+                        overlay_locations,
+                        &overlay_key_path,
+                    ),
+                    None => {
+                        locations.replace(&key_path, overlay_locations, &overlay_key_path);
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => {
+            locations.replace(path, overlay_locations, overlay_path);
+            *base = overlay;
+        }
+    }
+}
+
+/// Merges groups by name and runners by name, the runners that are new are added to the group
+/// they are listed in, or to a new group when that group doesn't exist yet
+fn merge_groups(
+    base: &mut Vec<Value>,
+    locations: &mut Locations,
+    overlay: Vec<Value>,
+    overlay_locations: &Locations,
+) {
+    for (group_index, group) in overlay.into_iter().enumerate() {
+        let group_path = format!("groups.{group_index}");
+        let mut group = match group {
+            Value::Mapping(group) if group.get("runners").is_none_or(Value::is_sequence) => group,
+            // Left for the validation to report
+            group => {
+                locations.replace(
+                    &format!("groups.{}", base.len()),
+                    overlay_locations,
+                    &group_path,
+                );
+                base.push(group);
+                continue;
+            }
+        };
+
+        let runners = match group.remove("runners") {
+            Some(Value::Sequence(runners)) => runners,
+            _ => vec![],
+        };
+        let mut new_runners = vec![];
+        for (runner_index, runner) in runners.into_iter().enumerate() {
+            let runner_path = format!("{group_path}.runners.{runner_index}");
+            let existing = runner
+                .get("name")
+                .and_then(Value::as_str)
+                .and_then(|name| find_runner(base, name));
+            match existing {
+                Some((base_group, base_runner)) => merge_value(
+                    &mut base[base_group]["runners"][base_runner],
+                    locations,
+                    &format!("groups.{base_group}.runners.{base_runner}"),
+                    runner,
+                    overlay_locations,
+                    &runner_path,
+                ),
+                None => new_runners.push((runner, runner_path)),
+            }
+        }
+
+        let name = group.get("name").and_then(Value::as_str);
+        let existing = name.and_then(|name| {
+            base.iter()
+                .position(|g| g.get("name").and_then(Value::as_str) == Some(name))
+        });
+        let base_index = match existing {
+            Some(base_index) => {
+                merge_value(
+                    &mut base[base_index],
+                    locations,
+                    &format!("groups.{base_index}"),
+                    Value::Mapping(group),
+                    overlay_locations,
+                    &group_path,
+                );
+                base_index
+            }
+            None if new_runners.is_empty() => continue,
+            None => {
+                let base_path = format!("groups.{}", base.len());
+                locations.replace(&base_path, overlay_locations, &group_path);
+                locations.remove_children(&format!("{base_path}.runners"));
+                group.insert("runners".into(), Value::Sequence(vec![]));
+                base.push(Value::Mapping(group));
+                base.len() - 1
+            }
+        };
+
+        let Some(base_runners) = base[base_index]
+            .get_mut("runners")
+            .and_then(Value::as_sequence_mut)
+        else {
+            continue;
+        };
+        for (runner, runner_path) in new_runners {
+            locations.replace(
+                &format!("groups.{base_index}.runners.{}", base_runners.len()),
+                overlay_locations,
+                &runner_path,
+            );
+            base_runners.push(runner);
+        }
+    }
+}
+
+/// Group and position of the runner with the name
+fn find_runner(groups: &[Value], name: &str) -> Option<(usize, usize)> {
+    groups.iter().enumerate().find_map(|(group_index, group)| {
+        let runners = group.get("runners")?.as_sequence()?;
+        let runner_index = runners
+            .iter()
+            .position(|r| r.get("name").and_then(Value::as_str) == Some(name))?;
+        Some((group_index, runner_index))
+    })
+}
+
+/// Removes the runners that have `enabled: false`, and the groups that are left without runners
+fn remove_disabled(merged: &mut Merged, file: &str) -> Vec<ConfigError> {
+    let mut errors = vec![];
+    let Some(groups) = merged
+        .value
+        .get_mut("groups")
+        .and_then(Value::as_sequence_mut)
+    else {
+        return errors;
+    };
+
+    for group_index in (0..groups.len()).rev() {
+        let group_path = format!("groups.{group_index}");
+        let Some(runners) = groups[group_index]
+            .get_mut("runners")
+            .and_then(Value::as_sequence_mut)
+        else {
+            continue;
+        };
+        if runners.is_empty() {
+            continue;
+        }
+
+        for runner_index in (0..runners.len()).rev() {
+            let runner_path = format!("{group_path}.runners.{runner_index}");
+            let Some(runner) = runners[runner_index].as_mapping_mut() else {
+                continue;
+            };
+            match runner.remove("enabled") {
+                None | Some(Value::Bool(true)) => (),
+                Some(Value::Bool(false)) => {
+                    remove_item(runners, &mut merged.locations, &runner_path, runner_index);
+                }
+                Some(_) => errors.push(ConfigError::at(
+                    merged.locations.find(&format!("{runner_path}.enabled")),
+                    file,
+                    "`enabled` has to be true or false".into(),
+                )),
+            }
+        }
+        if runners.is_empty() {
+            remove_item(groups, &mut merged.locations, &group_path, group_index);
+        }
+    }
+    errors
+}
+
+/// Expands the variables in the `dir`, `args` and `env` values of the runners. A variable is
+/// looked up in the built-ins `CONFIG_DIR` and `RUNNER_NAME`, then in the environment, then under
+/// `vars:`, whose own values can use the environment and `CONFIG_DIR`. The environment is read
+/// with `env_var`
+fn interpolate(
+    merged: &mut Merged,
+    file: &str,
+    env_var: &dyn Fn(&str) -> Option<String>,
+) -> Vec<ConfigError> {
+    let mut errors = vec![];
+    let mut error = |path: &str, message: String| {
+        errors.push(ConfigError::at(merged.locations.find(path), file, message))
+    };
+    let config_dir = fs::canonicalize(file)
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+        .display()
+        .to_string();
+    let Value::Mapping(root) = &mut merged.value else {
+        return errors;
+    };
+
+    // The variables are only used here, so they are not part of the parsed config
+    let mut vars: HashMap<String, String> = HashMap::new();
+    match root.remove("vars") {
+        Some(Value::Mapping(values)) => {
+            let lookup = |name: &str| match name {
+                "CONFIG_DIR" => Some(config_dir.clone()),
+                _ => env_var(name),
+            };
+            for (name, value) in values {
+                let name = name.as_str().unwrap_or_default().to_string();
+                let path = format!("vars.{name}");
+                match scalar_text(&value) {
+                    Some(text) => match expand(&text, &lookup) {
+                        Ok(expanded) => drop(vars.insert(name, expanded)),
+                        Err(message) => error(&path, message),
+                    },
+                    None => error(&path, "a variable has to be a text or a number".into()),
+                }
+            }
+        }
+        Some(_) => error(
+            "vars",
+            "`vars` has to be a mapping of names to values".into(),
+        ),
+        None => (),
+    }
+
+    let Some(Value::Sequence(groups)) = root.get_mut("groups") else {
+        return errors;
+    };
+    for (group_index, group) in groups.iter_mut().enumerate() {
+        let Some(Value::Sequence(runners)) = group.get_mut("runners") else {
+            continue;
+        };
+        for (runner_index, runner) in runners.iter_mut().enumerate() {
+            let runner_path = format!("groups.{group_index}.runners.{runner_index}");
+            let runner_name = runner
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let lookup = |name: &str| match name {
+                "CONFIG_DIR" => Some(config_dir.clone()),
+                "RUNNER_NAME" => Some(runner_name.clone()),
+                _ => env_var(name).or_else(|| vars.get(name).cloned()),
+            };
+            let mut expand_value = |value: &mut Value, path: String| {
+                // Numbers are allowed in `env` and turned into text, other types are left for
+                // the parsing to report
+                if let Some(text) = scalar_text(value).filter(|_| path.contains(".env.")) {
+                    *value = Value::String(text);
+                }
+                if let Value::String(text) = value {
+                    match expand(text, &lookup) {
+                        Ok(expanded) => *text = expanded,
+                        Err(message) => error(&path, message),
+                    }
+                }
+            };
+
+            if let Some(dir) = runner.get_mut("dir") {
+                expand_value(dir, format!("{runner_path}.dir"));
+            }
+            if let Some(Value::Sequence(args)) = runner.get_mut("args") {
+                for (arg_index, arg) in args.iter_mut().enumerate() {
+                    expand_value(arg, format!("{runner_path}.args.{arg_index}"));
+                }
+            }
+            if let Some(Value::Mapping(env)) = runner.get_mut("env") {
+                for (name, value) in env.iter_mut() {
+                    let name = name.as_str().unwrap_or_default();
+                    expand_value(value, format!("{runner_path}.env.{name}"));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Makes the runner directories and the debug log file absolute. Relative paths are resolved
+/// against the directory of the file they are written in, or against the working directory with
+/// `relative_paths: cwd`. An empty directory stays empty and runs the process in the working
+/// directory
+fn resolve_paths(merged: &mut Merged, file: &str) -> Vec<ConfigError> {
+    let mut errors = vec![];
+    let Value::Mapping(root) = &mut merged.value else {
+        return errors;
+    };
+    // Only used here, so it is not part of the parsed config
+    let from_cwd = match root.remove("relative_paths") {
+        None => false,
+        Some(Value::String(base)) if base == "config" => false,
+        Some(Value::String(base)) if base == "cwd" => true,
+        Some(_) => {
+            errors.push(ConfigError::at(
+                merged.locations.find("relative_paths"),
+                file,
+                "`relative_paths` has to be `config` or `cwd`".into(),
+            ));
+            false
+        }
+    };
+
+    let locations = &merged.locations;
+    let resolve = |value: &mut Value, path: &str| {
+        let Value::String(text) = value else {
+            return;
+        };
+        if text.is_empty() || Path::new(text.as_str()).is_absolute() {
+            return;
+        }
+        let base = match locations.find(path) {
+            Some(location) if !from_cwd => Path::new(&location.file)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            _ => PathBuf::new(),
+        };
+        let joined = base.join(text.as_str());
+        let resolved = fs::canonicalize(&joined)
+            .or_else(|_| std::path::absolute(&joined))
+            .unwrap_or(joined);
+        *text = resolved.display().to_string();
+    };
+
+    if let Some(debug_file) = root.get_mut("debug").and_then(|d| d.get_mut("file")) {
+        resolve(debug_file, "debug.file");
+    }
+    let Some(Value::Sequence(groups)) = root.get_mut("groups") else {
+        return errors;
+    };
+    for (group_index, group) in groups.iter_mut().enumerate() {
+        let Some(Value::Sequence(runners)) = group.get_mut("runners") else {
+            continue;
+        };
+        for (runner_index, runner) in runners.iter_mut().enumerate() {
+            if let Some(dir) = runner.get_mut("dir") {
+                resolve(
+                    dir,
+                    &format!("groups.{group_index}.runners.{runner_index}.dir"),
+                );
+            }
+        }
+    }
+    errors
+}
+
+/// Text of a string, number or boolean value
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// Expands `${NAME}` and `${NAME:-default}`, where the default is used when the variable is
+/// unset or empty. `$${` is a literal `${`, and `$` without braces is left as it is
+fn expand(text: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if after.starts_with("${") {
+            expanded.push('$');
+            rest = &after[1..];
+            continue;
+        }
+        let Some(reference) = after.strip_prefix('{') else {
+            expanded.push('$');
+            rest = after;
+            continue;
+        };
+        let end = reference
+            .find('}')
+            .ok_or_else(|| format!("`${{` without a closing `}}` in `{text}`"))?;
+        let (name, default) = match reference[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&reference[..end], None),
+        };
+        let valid_name = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && name.chars().next().is_some_and(|c| !c.is_ascii_digit());
+        if !valid_name {
+            return Err(format!("`{name}` is not a valid variable name"));
+        }
+
+        let value = lookup(name).filter(|value| default.is_none() || !value.is_empty());
+        match (value, default) {
+            (Some(value), _) => expanded.push_str(&value),
+            (None, Some(default)) => expanded.push_str(default),
+            (None, None) => {
+                return Err(format!(
+                    "undefined variable `{name}`, set it in the environment or under `vars:`"
+                ))
+            }
+        }
+        rest = &reference[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Removes an item from a sequence, moving the locations of the items after it
+fn remove_item(items: &mut Vec<Value>, locations: &mut Locations, path: &str, index: usize) {
+    let parent = &path[..path.rfind('.').unwrap_or(0)];
+    items.remove(index);
+    locations.remove(path);
+    for moved in index..items.len() {
+        locations.rename(
+            &format!("{parent}.{}", moved + 1),
+            &format!("{parent}.{moved}"),
+        );
+    }
+}
+
+/// Reads, merges, parses and validates a config file, every problem that is found is returned
+/// at once
+pub fn load(file: &str, format: Option<Format>) -> Result<Cfg, Vec<ConfigError>> {
+    let merged = merge_layers(file, format)?;
+    let locations = merged.locations;
+    let files = merged.files;
+    let merge_errors = merged.errors;
+
+    let mut unknown_fields = vec![];
+    let mut on_unknown = |path: serde_ignored::Path| unknown_fields.push(path.to_string());
+    let parsed: Result<Cfg, _> = serde_path_to_error::deserialize(
+        serde_ignored::Deserializer::new(merged.value, &mut on_unknown),
+    );
+    let mut errors: Vec<ConfigError> = unknown_fields
+        .iter()
+        .map(|path| {
+            let name = path.rsplit('.').next().unwrap_or(path);
+            ConfigError::at(
+                locations.find(path),
+                file,
+                format!("unknown field `{name}` at `{path}`"),
+            )
+        })
+        .collect();
+
+    // A value that couldn't be expanded is already reported, so it isn't validated again
+    let reported = |error: &ConfigError| {
+        merge_errors
+            .iter()
+            .any(|e| e.file == error.file && e.position == error.position)
+    };
+    match parsed {
+        Ok(mut cfg) => {
+            errors.extend(
+                validate(&cfg, &locations)
+                    .into_iter()
+                    .map(|(path, message)| ConfigError::at(locations.find(&path), file, message))
+                    .filter(|error| !reported(error)),
+            );
+            errors.extend(merge_errors.iter().cloned());
+            if errors.is_empty() {
+                for (group_index, group) in cfg.groups.iter_mut().enumerate() {
+                    for (runner_index, runner) in group.runners.iter_mut().enumerate() {
+                        let path = format!("groups.{group_index}.runners.{runner_index}");
+                        runner.files = locations.files_under(&path, &files);
+                    }
+                }
+                cfg.files = files;
+                let local = local_override(Path::new(file));
+                if !cfg.files.contains(&local) {
+                    cfg.files.push(local);
+                }
+                Ok(cfg)
+            } else {
+                sort(&mut errors);
+                Err(errors)
+            }
+        }
+        Err(err) => {
+            let path = err.path().to_string().replace('[', ".").replace(']', "");
+            let error = ConfigError::at(locations.find(&path), file, err.into_inner().to_string());
+            if !reported(&error) {
+                errors.push(error);
+            }
+            errors.extend(merge_errors.iter().cloned());
+            sort(&mut errors);
+            Err(errors)
+        }
+    }
+}
+
+/// Reads, merges, parses and validates a config file like [`load`], then applies the selection
+/// from the command line
+pub fn load_filtered(
+    file: &str,
+    format: Option<Format>,
+    overrides: &Overrides,
+) -> Result<Cfg, Vec<ConfigError>> {
+    let mut cfg = load(file, format)?;
+    overrides.apply(&mut cfg).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|message| ConfigError::at(None, file, message))
+            .collect::<Vec<_>>()
+    })?;
+    Ok(cfg)
+}
+
+/// The merged config as YAML with the selection from the command line applied, preceded by the
+/// files it was merged from. The config is validated first
+pub fn print_merged(
+    file: &str,
+    format: Option<Format>,
+    overrides: &Overrides,
+) -> Result<String, Vec<ConfigError>> {
+    let cfg = load_filtered(file, format, overrides)?;
+    let mut merged = merge_layers(file, format)?;
+    select_runners(&mut merged.value, &cfg, overrides.no_auto_start);
+    let mut output = String::from("# Merged from, in this order:\n");
+    for layer_file in &merged.files {
+        output.push_str(&format!("#   {}\n", layer_file.display()));
+    }
+    let yaml = serde_yaml::to_string(&merged.value)
+        .map_err(|err| vec![ConfigError::at(None, file, err.to_string())])?;
+    output.push_str(&yaml);
+    Ok(output)
+}
+
+/// Keeps the groups and runners of the merged document that are left in the filtered config
+fn select_runners(value: &mut Value, cfg: &Cfg, no_auto_start: bool) {
+    let Some(groups) = value.get_mut("groups").and_then(Value::as_sequence_mut) else {
+        return;
+    };
+    let mut selected = vec![];
+    for (group_index, mut group) in std::mem::take(groups).into_iter().enumerate() {
+        let name = group
+            .get("name")
+            .and_then(Value::as_str)
+            .map_or_else(|| format!("Group {}", group_index + 1), str::to_string);
+        let Some(kept) = cfg
+            .groups
+            .iter()
+            .find(|g| g.name.as_deref() == Some(name.as_str()))
+        else {
+            continue;
+        };
+        if let Some(runners) = group.get_mut("runners").and_then(Value::as_sequence_mut) {
+            runners.retain(|runner| {
+                let runner_name = runner.get("name").and_then(Value::as_str);
+                kept.runners
+                    .iter()
+                    .any(|r| Some(r.name.as_str()) == runner_name)
+            });
+            if no_auto_start {
+                for runner in runners.iter_mut().filter_map(Value::as_mapping_mut) {
+                    runner.insert("auto_start".into(), false.into());
+                }
+            }
+        }
+        // A group without a name would get the default name of its new position
+        if let Some(group) = group
+            .as_mapping_mut()
+            .filter(|_| selected.len() != group_index)
+        {
+            if !group.contains_key("name") {
+                group.insert("name".into(), name.into());
+            }
+        }
+        selected.push(group);
+    }
+    *groups = selected;
+}
+
+/// JSON Schema of the config for editor completion, including the settings that are handled
+/// while the files are merged
+pub fn schema() -> String {
+    let mut schema = schemars::schema_for!(Cfg);
+    if let Some(properties) = schema
+        .get_mut("properties")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        properties.insert(
+            "include".into(),
+            serde_json::json!({
+                "description": "Config files merged before this one, relative to this file",
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } }
+                ]
+            }),
+        );
+        properties.insert(
+            "vars".into(),
+            serde_json::json!({
+                "description": "Variables for `${NAME}` in runner values, the environment takes precedence over them",
+                "type": "object",
+                "additionalProperties": { "type": ["string", "number", "boolean"] }
+            }),
+        );
+        properties.insert(
+            "relative_paths".into(),
+            serde_json::json!({
+                "description": "What relative paths are resolved against, the directory of the file they are written in or the working directory",
+                "enum": ["config", "cwd"],
+                "default": "config"
+            }),
+        );
+    }
+    if let Some(properties) = schema
+        .pointer_mut("/$defs/RunnerCfg/properties")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        properties.insert(
+            "enabled".into(),
+            serde_json::json!({
+                "description": "Set to false to remove the runner, e.g. from a local override",
+                "type": "boolean",
+                "default": true
+            }),
+        );
+    }
+    serde_json::to_string_pretty(&schema).unwrap_or_default() + "\n"
+}
+
+fn sort(errors: &mut [ConfigError]) {
+    errors.sort_by(|a, b| (&a.file, a.position).cmp(&(&b.file, b.position)));
+}
+
+/// Drops the position serde_yaml adds to its messages, when it is reported separately
+fn without_position(message: String, position: Option<(usize, usize)>) -> String {
+    match message.rfind(" at line ") {
+        Some(end) if position.is_some() => message[..end].to_string(),
+        _ => message,
+    }
+}
+
+/// Checks what the config format itself can't express, returning the path of the value each
+/// problem is about together with the message
+fn validate(cfg: &Cfg, locations: &Locations) -> Vec<(String, String)> {
+    let mut errors = vec![];
+
+    if let Err(key_errors) = Keymap::from_config(&cfg.keys) {
+        errors.extend(
+            key_errors
+                .into_iter()
+                .map(|(action, err)| (format!("keys.{action}"), err)),
+        );
+    }
+    if let Err(theme_errors) = Theme::from_config(&cfg.theme) {
+        errors.extend(
+            theme_errors
+                .into_iter()
+                .map(|(colour, err)| (format!("theme.colors.{colour}"), err)),
+        );
+    }
+    if cfg.groups.is_empty() {
+        errors.push(("groups".into(), "at least one group is needed".into()));
+    }
+
+    let mut names: HashMap<&str, String> = HashMap::new();
+    for (group_index, group) in cfg.groups.iter().enumerate() {
+        let group_path = format!("groups.{group_index}");
+        if group.runners.is_empty() {
+            errors.push((
+                format!("{group_path}.runners"),
+                "a group needs at least one runner".into(),
+            ));
+        }
+
+        for (runner_index, runner) in group.runners.iter().enumerate() {
+            let path = format!("{group_path}.runners.{runner_index}");
+            if runner.name.trim().is_empty() {
+                errors.push((format!("{path}.name"), "the runner name is empty".into()));
+            } else if let Some(first) = names.get(runner.name.as_str()) {
+                let first_line = locations.find(first).map_or(String::new(), |l| {
+                    format!(" at {}:{}", l.file, l.position.0)
+                });
+                errors.push((
+                    format!("{path}.name"),
+                    format!(
+                        "duplicate runner name `{}`, it is already used{first_line}",
+                        runner.name
+                    ),
+                ));
+            } else {
+                names.insert(&runner.name, format!("{path}.name"));
+            }
+
+            if runner.args.first().is_none_or(|lead| lead.is_empty()) {
+                errors.push((
+                    format!("{path}.args"),
+                    "`args` needs at least the command to run".into(),
+                ));
+            }
+            if !runner.dir.is_empty() && !Path::new(&runner.dir).is_dir() {
+                errors.push((
+                    format!("{path}.dir"),
+                    format!("the directory `{}` does not exist", runner.dir),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// Line and column of every mapping key and sequence item in a YAML document, by their dotted
+/// path like `groups.0.runners.1.name`
+#[derive(Default)]
+struct Locations {
+    positions: HashMap<String, Location>,
+    /// File that is being parsed
+    file: String,
+    stack: Vec<Node>,
+}
+
+/// File, line and column a value was read from
+#[derive(Debug, Clone)]
+struct Location {
+    file: String,
+    position: (usize, usize),
+}
+
+/// A mapping or sequence that is being parsed
+enum Node {
+    Mapping {
+        path: String,
+        /// Key of the value that is parsed next, unset while a key is expected
+        key: Option<String>,
+    },
+    Sequence {
+        path: String,
+        index: usize,
+    },
+}
+
+impl Locations {
+    fn parse(file: &str, contents: &str) -> Result<Self, yaml_rust2::ScanError> {
+        let mut locations = Locations {
+            file: file.to_string(),
+            ..Default::default()
+        };
+        Parser::new_from_str(contents).load(&mut locations, false)?;
+        Ok(locations)
+    }
+
+    /// Position of the path, or of its closest parent that has one
+    fn find(&self, path: &str) -> Option<Location> {
+        // Optional values add a `?` to the paths reported for unknown fields
+        let mut path = path
+            .split('.')
+            .filter(|part| *part != "?")
+            .collect::<Vec<&str>>()
+            .join(".");
+        loop {
+            if let Some(location) = self.positions.get(&path) {
+                return Some(location.clone());
+            }
+            path.truncate(path.rfind('.')?);
+        }
+    }
+
+    /// Records the locations of the keys and array items of a TOML table
+    fn add_toml_table(&mut self, contents: &str, path: &str, table: &toml_edit::Table) {
+        for (key, item) in table.iter() {
+            let key_path = join(path, key);
+            if let Some(span) = table.key(key).and_then(|k| k.span()) {
+                self.add_toml_span(contents, &key_path, span);
+            }
+            match item {
+                toml_edit::Item::Table(table) => self.add_toml_table(contents, &key_path, table),
+                toml_edit::Item::ArrayOfTables(tables) => {
+                    for (index, table) in tables.iter().enumerate() {
+                        let item_path = join(&key_path, &index.to_string());
+                        if let Some(span) = table.span() {
+                            self.add_toml_span(contents, &item_path, span);
+                        }
+                        self.add_toml_table(contents, &item_path, table);
+                    }
+                }
+                toml_edit::Item::Value(value) => self.add_toml_value(contents, &key_path, value),
+                toml_edit::Item::None => (),
+            }
+        }
+    }
+
+    fn add_toml_value(&mut self, contents: &str, path: &str, value: &toml_edit::Value) {
+        match value {
+            toml_edit::Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let item_path = join(path, &index.to_string());
+                    if let Some(span) = item.span() {
+                        self.add_toml_span(contents, &item_path, span);
+                    }
+                    self.add_toml_value(contents, &item_path, item);
+                }
+            }
+            toml_edit::Value::InlineTable(table) => {
+                for (key, item) in table.iter() {
+                    let key_path = join(path, key);
+                    if let Some(span) = table.key(key).and_then(|k| k.span()) {
+                        self.add_toml_span(contents, &key_path, span);
+                    }
+                    self.add_toml_value(contents, &key_path, item);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn add_toml_span(&mut self, contents: &str, path: &str, span: Range<usize>) {
+        self.positions.entry(path.to_string()).or_insert(Location {
+            file: self.file.clone(),
+            position: line_column(contents, span.start),
+        });
+    }
+
+    /// Replaces the locations of `path` and everything under it by those of `other_path` in
+    /// `other`
+    /// Files that set the path or anything below it, starting with the file of the path itself
+    /// and followed by the others in layering order
+    fn files_under(&self, path: &str, layers: &[PathBuf]) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .positions
+            .get(path)
+            .map(|l| PathBuf::from(&l.file))
+            .into_iter()
+            .collect();
+        for layer in layers {
+            let name = layer.display().to_string();
+            let sets = self
+                .positions
+                .iter()
+                .any(|(p, l)| l.file == name && strip_path(p, path).is_some());
+            if sets && !files.contains(layer) {
+                files.push(layer.clone());
+            }
+        }
+        files
+    }
+
+    fn replace(&mut self, path: &str, other: &Locations, other_path: &str) {
+        self.remove(path);
+        for (location_path, location) in &other.positions {
+            if let Some(rest) = strip_path(location_path, other_path) {
+                self.positions
+                    .insert(format!("{path}{rest}"), location.clone());
+            }
+        }
+    }
+
+    fn remove(&mut self, path: &str) {
+        self.positions
+            .retain(|location_path, _| strip_path(location_path, path).is_none());
+    }
+
+    /// Removes the locations under `path`, but not the one of `path` itself
+    fn remove_children(&mut self, path: &str) {
+        self.positions.retain(|location_path, _| {
+            strip_path(location_path, path).is_none_or(|rest| rest.is_empty())
+        });
+    }
+
+    fn rename(&mut self, from: &str, to: &str) {
+        let moved: Vec<String> = self
+            .positions
+            .keys()
+            .filter(|location_path| strip_path(location_path, from).is_some())
+            .cloned()
+            .collect();
+        for location_path in moved {
+            if let Some(location) = self.positions.remove(&location_path) {
+                let rest = &location_path[from.len()..];
+                self.positions.insert(format!("{to}{rest}"), location);
+            }
+        }
+    }
+
+    /// Path of the value that starts now, which is recorded for sequence items
+    fn next_value_path(&mut self, mark: Marker) -> String {
+        let location = Location {
+            file: self.file.clone(),
+            position: (mark.line(), mark.col() + 1),
+        };
+        match self.stack.last_mut() {
+            Some(Node::Mapping { path, key }) => join(path, key.as_deref().unwrap_or_default()),
+            Some(Node::Sequence { path, index }) => {
+                let item_path = join(path, &index.to_string());
+                self.positions.entry(item_path.clone()).or_insert(location);
+                item_path
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Moves the parent on to its next key or item once a value is complete
+    fn end_value(&mut self) {
+        match self.stack.last_mut() {
+            Some(Node::Mapping { key, .. }) => *key = None,
+            Some(Node::Sequence { index, .. }) => *index += 1,
+            None => (),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Locations {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                if let Some(Node::Mapping {
+                    path,
+                    key: key @ None,
+                }) = self.stack.last_mut()
+                {
+                    let key_path = join(path, &value);
+                    self.positions.entry(key_path).or_insert(Location {
+                        file: self.file.clone(),
+                        position: (mark.line(), mark.col() + 1),
+                    });
+                    *key = Some(value);
+                } else {
+                    self.next_value_path(mark);
+                    self.end_value();
+                }
+            }
+            Event::Alias(_) => {
+                self.next_value_path(mark);
+                self.end_value();
+            }
+            Event::MappingStart(..) => {
+                let path = self.next_value_path(mark);
+                self.stack.push(Node::Mapping { path, key: None });
+            }
+            Event::SequenceStart(..) => {
+                let path = self.next_value_path(mark);
+                self.stack.push(Node::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.end_value();
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Line and column of a byte offset, both starting at 1
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// The rest of `path` after `prefix`, when `path` is `prefix` or under it
+fn strip_path<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    path.strip_prefix(prefix)
+        .filter(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+fn join(parent: &str, child: &str) -> String {
+    if parent.is_empty() {
+        child.to_string()
+    } else {
+        format!("{parent}.{child}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the files to a new directory for the test and returns the directory
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("control-panel-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn path(dir: &Path, name: &str) -> String {
+        dir.join(name).display().to_string()
+    }
+
+    fn runner_names(cfg: &Cfg) -> Vec<Vec<&str>> {
+        cfg.groups
+            .iter()
+            .map(|g| g.runners.iter().map(|r| r.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn local_override_merges_runners_by_name() {
+        let dir = write_files(
+            "override",
+            &[
+                (
+                    "config.yml",
+                    "groups:\n  - name: web\n    runners:\n      - name: a\n        dir: \"\"\n        args: [a]\n      - name: b\n        dir: \"\"\n        args: [b]\n",
+                ),
+                (
+                    "config.local.yml",
+                    "groups:\n  - runners:\n      - name: b\n        args: [b, --local]\n",
+                ),
+            ],
+        );
+        let config = path(&dir, "config.yml");
+        let merged = merge_layers(&config, None).ok().unwrap();
+        let args = merged.locations.find("groups.0.runners.1.args").unwrap();
+        assert_eq!(args.file, path(&dir, "config.local.yml"));
+        assert_eq!(args.position, (4, 9));
+        let name = merged.locations.find("groups.0.runners.1.dir").unwrap();
+        assert_eq!(name.file, config);
+
+        let cfg = load(&config, None).unwrap();
+        assert_eq!(runner_names(&cfg), vec![vec!["a", "b"]]);
+        assert_eq!(cfg.groups[0].runners[0].args, vec!["a"]);
+        assert_eq!(cfg.groups[0].runners[1].args, vec!["b", "--local"]);
+        assert_eq!(cfg.files.last().unwrap(), &dir.join("config.local.yml"));
+        assert_eq!(cfg.groups[0].runners[0].files, vec![dir.join("config.yml")]);
+        assert_eq!(
+            cfg.groups[0].runners[1].files,
+            vec![dir.join("config.yml"), dir.join("config.local.yml")]
+        );
+    }
+
+    #[test]
+    fn disabled_runners_are_removed_and_later_positions_move() {
+        let dir = write_files(
+            "disabled",
+            &[(
+                "config.yml",
+                "groups:\n  - runners:\n      - name: a\n        dir: \"\"\n        args: [a]\n        enabled: false\n  - runners:\n      - name: b\n        dir: \"\"\n        args: [b]\n        enabled: false\n      - name: c\n        dir: \"\"\n        args: [c]\n        bogus: 1\n",
+            )],
+        );
+        let errors = load(&path(&dir, "config.yml"), None).err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "unknown field `bogus` at `groups.0.runners.0.bogus`"
+        );
+        assert_eq!(errors[0].position, Some((15, 9)));
+
+        let merged = merge_layers(&path(&dir, "config.yml"), None).ok().unwrap();
+        assert_eq!(merged.value["groups"].as_sequence().unwrap().len(), 1);
+        let name = merged.locations.find("groups.0.runners.0.name").unwrap();
+        assert_eq!(name.position, (12, 9));
+    }
+
+    #[test]
+    fn new_runners_are_added_to_the_group_with_the_same_name() {
+        let dir = write_files(
+            "new-runner",
+            &[
+                (
+                    "base.yml",
+                    "groups:\n  - name: web\n    runners:\n      - name: a\n        dir: \"\"\n        args: [a]\n  - name: tools\n    runners:\n      - name: b\n        dir: \"\"\n        args: [b]\n",
+                ),
+                (
+                    "config.yml",
+                    "include: base.yml\ngroups:\n  - name: tools\n    runners:\n      - name: c\n        dir: \"\"\n        args: [c]\n  - name: extra\n    runners:\n      - name: d\n        dir: \"\"\n        args: [d]\n",
+                ),
+            ],
+        );
+        let config = path(&dir, "config.yml");
+        let cfg = load(&config, None).unwrap();
+        assert_eq!(
+            runner_names(&cfg),
+            vec![vec!["a"], vec!["b", "c"], vec!["d"]]
+        );
+        assert_eq!(cfg.groups[2].name.as_deref(), Some("extra"));
+        assert_eq!(cfg.groups[1].runners[0].files, vec![dir.join("base.yml")]);
+        assert_eq!(cfg.groups[1].runners[1].files, vec![dir.join("config.yml")]);
+
+        let merged = merge_layers(&config, None).ok().unwrap();
+        let added = merged.locations.find("groups.1.runners.1.name").unwrap();
+        assert_eq!((added.file, added.position), (config.clone(), (5, 9)));
+        let group = merged.locations.find("groups.2.name").unwrap();
+        assert_eq!((group.file, group.position), (config, (8, 5)));
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let dir = write_files(
+            "cycle",
+            &[
+                ("a.yml", "include: b.yml\ngroups: []\n"),
+                ("b.yml", "include: [c.yml]\n"),
+                ("c.yml", "include:\n  - a.yml\n"),
+                ("self.yml", "include: self.yml\n"),
+            ],
+        );
+        let errors = load(&path(&dir, "a.yml"), None).err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file, path(&dir, "c.yml"));
+        assert_eq!(errors[0].position, Some((2, 5)));
+        assert_eq!(
+            errors[0].message,
+            "`a.yml` includes this file again, includes can't form a cycle"
+        );
+
+        let errors = load(&path(&dir, "self.yml"), None).err().unwrap();
+        assert_eq!(errors[0].position, Some((1, 1)));
+    }
+
+    #[test]
+    fn missing_includes_are_reported_at_the_include() {
+        let dir = write_files(
+            "missing-include",
+            &[
+                ("config.yml", "include:\n  - other.yml\n  - missing.yml\n"),
+                ("other.yml", "groups: []\n"),
+            ],
+        );
+        let errors = load(&path(&dir, "config.yml"), None).err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file, path(&dir, "config.yml"));
+        assert_eq!(errors[0].position, Some((3, 5)));
+        assert!(errors[0]
+            .message
+            .starts_with("cannot read the included file `missing.yml`: "));
+    }
+
+    fn expand_with(text: &str, vars: &[(&str, &str)]) -> Result<String, String> {
+        let lookup = |name: &str| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        };
+        expand(text, &lookup)
+    }
+
+    #[test]
+    fn expands_variables_and_defaults() {
+        let vars = [("A", "a"), ("EMPTY", "")];
+        assert_eq!(expand_with("${A}/${A}", &vars).unwrap(), "a/a");
+        assert_eq!(expand_with("${B:-b}", &vars).unwrap(), "b");
+        assert_eq!(expand_with("${EMPTY:-e}", &vars).unwrap(), "e");
+        assert_eq!(expand_with("${EMPTY}", &vars).unwrap(), "");
+        assert_eq!(expand_with("${B:-}", &vars).unwrap(), "");
+        assert_eq!(expand_with("$A $ ${A}$", &vars).unwrap(), "$A $ a$");
+    }
+
+    #[test]
+    fn double_dollar_is_a_literal() {
+        assert_eq!(expand_with("$${A}", &[("A", "a")]).unwrap(), "${A}");
+        assert_eq!(expand_with("$$${A}", &[("A", "a")]).unwrap(), "$${A}");
+    }
+
+    #[test]
+    fn rejects_bad_references() {
+        assert_eq!(
+            expand_with("x ${A", &[("A", "a")]).unwrap_err(),
+            "`${` without a closing `}` in `x ${A`"
+        );
+        for name in ["", "1A", "A-B", "A B"] {
+            assert_eq!(
+                expand_with(&format!("${{{name}}}"), &[]).unwrap_err(),
+                format!("`{name}` is not a valid variable name")
+            );
+        }
+        assert!(expand_with("${UNSET}", &[])
+            .unwrap_err()
+            .starts_with("undefined variable `UNSET`"));
+    }
+
+    #[test]
+    fn environment_takes_precedence_over_vars() {
+        let contents = "vars:\n  FROM_ENV: vars\n  FROM_VARS: ${FROM_ENV}-vars\ngroups:\n  - runners:\n      - name: a\n        dir: \"\"\n        args: [\"${FROM_ENV}\", \"${FROM_VARS}\", \"${RUNNER_NAME}\"]\n";
+        let mut merged = Merged {
+            value: serde_yaml::from_str(contents).unwrap(),
+            locations: Locations::parse("config.yml", contents).unwrap(),
+            files: vec![],
+            errors: vec![],
+        };
+        let env_var = |name: &str| (name == "FROM_ENV").then(|| "env".to_string());
+        let errors = interpolate(&mut merged, "config.yml", &env_var);
+        assert!(errors.is_empty());
+        assert!(merged.value.get("vars").is_none());
+        let args: Vec<String> =
+            serde_yaml::from_value(merged.value["groups"][0]["runners"][0]["args"].clone())
+                .unwrap();
+        assert_eq!(args, vec!["env", "env-vars", "a"]);
+    }
+
+    #[test]
+    fn interpolation_errors_are_reported_with_the_other_problems() {
+        let dir = write_files(
+            "undefined",
+            &[(
+                "config.yml",
+                "groups:\n  - runners:\n      - name: a\n        dir: \"${CP_TEST_UNDEFINED}\"\n        args: []\n",
+            )],
+        );
+        let errors = load(&path(&dir, "config.yml"), None).err().unwrap();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "undefined variable `CP_TEST_UNDEFINED`, set it in the environment or under `vars:`",
+                "`args` needs at least the command to run",
+            ]
+        );
+    }
+
+    #[test]
+    fn procfile_processes_get_the_env_file_and_their_own_port() {
+        let dir = write_files(
+            "procfile",
+            &[
+                (
+                    "Procfile",
+                    "# processes\n\nweb: bundle exec puma -p $PORT\nworker:  echo ${HOME}:done \n",
+                ),
+                (".env", "PORT=7000\nSECRET=\"a b\"\n"),
+            ],
+        );
+        let file = dir.join("Procfile");
+        let processes = parse_procfile(&file, &fs::read_to_string(&file).unwrap())
+            .ok()
+            .unwrap();
+        let summary: Vec<(&str, &str, usize)> = processes
+            .iter()
+            .map(|p| (p.name.as_str(), p.command.as_str(), p.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("web", "bundle exec puma -p $PORT", 3),
+                ("worker", "echo ${HOME}:done", 4),
+            ]
+        );
+        let env = |key: &str, value: &str| (key.to_string(), value.to_string());
+        assert_eq!(
+            processes[1].env,
+            vec![env("SECRET", "a b"), env("PORT", "7100")]
+        );
+
+        let cfg = load(&file.display().to_string(), None).unwrap();
+        let runner = &cfg.groups[0].runners[1];
+        assert_eq!(runner.args, vec!["sh", "-c", "echo ${HOME}:done"]);
+        assert_eq!(runner.env["PORT"], "7100");
+        assert_eq!(
+            runner.dir,
+            fs::canonicalize(&dir).unwrap().display().to_string()
+        );
+    }
+
+    #[test]
+    fn malformed_procfile_lines_are_reported() {
+        let dir = write_files(
+            "bad-procfile",
+            &[("Procfile", "web: run\nno command\nempty:\n")],
+        );
+        let file = dir.join("Procfile");
+        let errors = parse_procfile(&file, &fs::read_to_string(&file).unwrap())
+            .err()
+            .unwrap();
+        let positions: Vec<_> = errors.iter().map(|e| e.position).collect();
+        assert_eq!(positions, vec![Some((2, 1)), Some((3, 1))]);
+        assert_eq!(errors[0].message, "expected a `name: command` line");
+    }
+
+    /// The errors of a config as `file:line:column: message`, with the file name only
+    fn error_lines(dir: &Path, name: &str) -> Vec<String> {
+        load(&path(dir, name), None)
+            .err()
+            .unwrap()
+            .iter()
+            .map(|e| e.to_string().replace(&format!("{}/", dir.display()), ""))
+            .collect()
+    }
+
+    #[test]
+    fn positions_are_found_in_every_format() {
+        let dir = write_files(
+            "formats",
+            &[
+                (
+                    "config.yml",
+                    "groups:\n  - name: web\n    runners:\n      - name: a\n        dir: /nope\n        args: [a]\n        bogus: 1\n      - name: b\n        dir: \"\"\n        args: [b]\n        size: big\n",
+                ),
+                (
+                    "config.toml",
+                    "[[groups]]\nname = \"web\"\n\n[[groups.runners]]\nname = \"a\"\ndir = \"/nope\"\nargs = [\"a\"]\nbogus = 1\n\n[[groups.runners]]\nname = \"b\"\ndir = \"\"\nargs = [\"b\"]\nsize = \"big\"\n",
+                ),
+                (
+                    "config.json",
+                    "{\n  \"groups\": [\n    {\n      \"name\": \"web\",\n      \"runners\": [\n        { \"name\": \"a\", \"dir\": \"/nope\", \"args\": [\"a\"], \"bogus\": 1 },\n        { \"name\": \"b\", \"dir\": \"\", \"args\": [\"b\"], \"size\": \"big\" }\n      ]\n    }\n  ]\n}\n",
+                ),
+            ],
+        );
+        // The size can't be parsed, so the config isn't validated
+        let size_error = "invalid type: string \"big\", expected u16";
+        assert_eq!(
+            error_lines(&dir, "config.yml"),
+            vec![
+                "config.yml:7:9: unknown field `bogus` at `groups.0.runners.0.bogus`".to_string(),
+                format!("config.yml:11:9: {size_error}"),
+            ]
+        );
+        assert_eq!(
+            error_lines(&dir, "config.toml"),
+            vec![
+                "config.toml:8:1: unknown field `bogus` at `groups.0.runners.0.bogus`".to_string(),
+                format!("config.toml:14:1: {size_error}"),
+            ]
+        );
+        assert_eq!(
+            error_lines(&dir, "config.json"),
+            vec![
+                "config.json:6:55: unknown field `bogus` at `groups.0.runners.0.bogus`".to_string(),
+                format!("config.json:7:50: {size_error}"),
+            ]
+        );
+
+        for name in ["config.yml", "config.toml", "config.json"] {
+            let contents = fs::read_to_string(dir.join(name)).unwrap();
+            let fixed = contents.replace("big", "2").replace("\"2\"", "2");
+            fs::write(dir.join(name), fixed.replace("bogus", "size")).unwrap();
+        }
+        let validated: Vec<String> = ["config.yml", "config.toml", "config.json"]
+            .iter()
+            .flat_map(|name| error_lines(&dir, name))
+            .collect();
+        assert_eq!(
+            validated,
+            vec![
+                "config.yml:5:9: the directory `/nope` does not exist",
+                "config.toml:6:1: the directory `/nope` does not exist",
+                "config.json:6:24: the directory `/nope` does not exist",
+            ]
+        );
+    }
+
+    /// A config with a named group and two unnamed ones, and the selection applied to it
+    fn filtered(test: &str, overrides: &Overrides) -> (PathBuf, Result<Cfg, Vec<String>>) {
+        let dir = write_files(
+            test,
+            &[(
+                "config.yml",
+                "groups:\n  - name: web\n    runners:\n      - name: a\n        dir: \"\"\n        args: [a]\n      - name: b\n        dir: \"\"\n        args: [b]\n  - runners:\n      - name: c\n        dir: \"\"\n        args: [c]\n  - runners:\n      - name: d\n        dir: \"\"\n        args: [d]\n",
+            )],
+        );
+        let cfg = load_filtered(&path(&dir, "config.yml"), None, overrides)
+            .map_err(|errors| errors.into_iter().map(|e| e.message).collect());
+        (dir, cfg)
+    }
+
+    fn group_names(cfg: &Cfg) -> Vec<String> {
+        cfg.groups
+            .iter()
+            .enumerate()
+            .map(|(index, group)| group.display_name(index))
+            .collect()
+    }
+
+    #[test]
+    fn only_keeps_the_named_runners_and_their_group_names() {
+        let overrides = Overrides {
+            only: vec!["a".into(), "d".into()],
+            ..Default::default()
+        };
+        let cfg = filtered("only", &overrides).1.unwrap();
+        assert_eq!(runner_names(&cfg), vec![vec!["a"], vec!["d"]]);
+        // The unnamed group keeps the default name of its position in the config
+        assert_eq!(group_names(&cfg), vec!["web", "Group 3"]);
+        assert!(cfg.groups[1].runners[0].auto_start);
+    }
+
+    #[test]
+    fn exclude_removes_the_named_runners() {
+        let overrides = Overrides {
+            exclude: vec!["b".into(), "c".into()],
+            ..Default::default()
+        };
+        let cfg = filtered("exclude", &overrides).1.unwrap();
+        assert_eq!(runner_names(&cfg), vec![vec!["a"], vec!["d"]]);
+        assert_eq!(group_names(&cfg), vec!["web", "Group 3"]);
+    }
+
+    #[test]
+    fn group_keeps_the_runners_of_the_named_groups() {
+        let overrides = Overrides {
+            groups: vec!["Group 3".into(), "web".into()],
+            exclude: vec!["a".into()],
+            ..Default::default()
+        };
+        let cfg = filtered("group", &overrides).1.unwrap();
+        assert_eq!(runner_names(&cfg), vec![vec!["b"], vec!["d"]]);
+        assert_eq!(group_names(&cfg), vec!["web", "Group 3"]);
+    }
+
+    #[test]
+    fn unknown_runner_and_group_names_are_reported() {
+        let overrides = Overrides {
+            only: vec!["a".into(), "x".into()],
+            exclude: vec!["y".into()],
+            groups: vec!["Group 4".into()],
+            ..Default::default()
+        };
+        let errors = filtered("unknown-names", &overrides).1.err().unwrap();
+        assert_eq!(
+            errors,
+            vec![
+                "there is no runner named `x`",
+                "there is no runner named `y`",
+                "there is no group named `Group 4`",
+            ]
+        );
+    }
+
+    #[test]
+    fn filtering_out_every_runner_is_reported() {
+        let overrides = Overrides {
+            only: vec!["a".into()],
+            groups: vec!["Group 2".into()],
+            ..Default::default()
+        };
+        let errors = filtered("none-left", &overrides).1.err().unwrap();
+        assert_eq!(errors, vec!["no runners are left after filtering"]);
+    }
+
+    #[test]
+    fn no_auto_start_applies_to_every_runner() {
+        let overrides = Overrides {
+            no_auto_start: true,
+            ..Default::default()
+        };
+        let cfg = filtered("no-auto-start", &overrides).1.unwrap();
+        assert_eq!(
+            runner_names(&cfg),
+            vec![vec!["a", "b"], vec!["c"], vec!["d"]]
+        );
+        assert!(cfg
+            .groups
+            .iter()
+            .flat_map(|g| &g.runners)
+            .all(|r| !r.auto_start));
+    }
+
+    #[test]
+    fn printed_config_has_the_selection_applied() {
+        let overrides = Overrides {
+            only: vec!["b".into(), "d".into()],
+            no_auto_start: true,
+            ..Default::default()
+        };
+        let (dir, _) = filtered("print-filtered", &overrides);
+        let config = path(&dir, "config.yml");
+        let output = print_merged(&config, None, &overrides).unwrap();
+        assert!(output.starts_with(&format!("# Merged from, in this order:\n#   {config}\n")));
+        let value: Value = serde_yaml::from_str(&output).unwrap();
+        let groups = value["groups"].as_sequence().unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0]["name"].as_str(), Some("web"));
+        // The moved group gets its old default name, so it isn't shown as `Group 2`
+        assert_eq!(groups[1]["name"].as_str(), Some("Group 3"));
+        let runners: Vec<_> = groups
+            .iter()
+            .flat_map(|g| g["runners"].as_sequence().unwrap())
+            .collect();
+        let names: Vec<_> = runners
+            .iter()
+            .map(|r| r["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["b", "d"]);
+        assert!(runners
+            .iter()
+            .all(|r| r["auto_start"].as_bool() == Some(false)));
+    }
+}
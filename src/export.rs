@@ -0,0 +1,346 @@
+use crate::models::{StdLine, Stream};
+
+use chrono::Utc;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// File format of an export, picked from the extension of the target path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Text,
+    JsonLines,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("jsonl") | Some("json") | Some("ndjson") => ExportFormat::JsonLines,
+            Some("html") | Some("htm") => ExportFormat::Html,
+            _ => ExportFormat::Text,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    timestamp: String,
+    stream: Stream,
+    content: &'a str,
+}
+
+/// Default export file name for a runner, e.g. `api-20240101-120000.log`
+pub fn default_file_name(runner_name: &str) -> String {
+    let name: String = runner_name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}-{}.log", name, Utc::now().format("%Y%m%d-%H%M%S"))
+}
+
+/// Writes the lines to `path` in the format matching its extension
+pub fn export_lines(
+    path: &Path,
+    title: &str,
+    lines: &[StdLine],
+    show_timestamps: bool,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    match ExportFormat::from_path(path) {
+        ExportFormat::Text => {
+            for line in lines {
+                let content = strip_ansi(&line.content);
+                if show_timestamps {
+                    writeln!(out, "[{}] {}", line.timestamp.to_rfc3339(), content)?;
+                } else {
+                    writeln!(out, "{content}")?;
+                }
+            }
+        }
+        ExportFormat::JsonLines => {
+            for line in lines {
+                serde_json::to_writer(
+                    &mut out,
+                    &JsonLine {
+                        timestamp: line.timestamp.to_rfc3339(),
+                        stream: line.stream,
+                        content: &line.content,
+                    },
+                )?;
+                writeln!(out)?;
+            }
+        }
+        ExportFormat::Html => {
+            writeln!(out, "<!DOCTYPE html>")?;
+            writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+            writeln!(out, "<title>{}</title>", escape_html(title))?;
+            writeln!(
+                out,
+                "<style>body{{background:#1e1e1e;color:#d4d4d4}}pre{{font-family:monospace}}\
+                 .ts{{color:#808080}}.stderr{{color:#f48771}}.marker{{color:#29b8db}}</style>"
+            )?;
+            writeln!(out, "</head><body><pre>")?;
+            for line in lines {
+                if show_timestamps {
+                    write!(
+                        out,
+                        "<span class=\"ts\">[{}]</span> ",
+                        line.timestamp.to_rfc3339()
+                    )?;
+                }
+                let content = ansi_to_html(&line.content);
+                match line.stream {
+                    Stream::Stdout => writeln!(out, "{content}")?,
+                    Stream::Stderr => writeln!(out, "<span class=\"stderr\">{content}</span>")?,
+                    Stream::Marker => writeln!(out, "<span class=\"marker\">{content}</span>")?,
+                }
+            }
+            writeln!(out, "</pre></body></html>")?;
+        }
+    }
+    out.flush()
+}
+
+/// Removes all escape sequences from the text
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            read_escape_sequence(&mut chars);
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Consumes an escape sequence after the ESC character and returns the parameters and the final
+/// character of a CSI sequence
+fn read_escape_sequence(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+) -> Option<(String, char)> {
+    if chars.peek() != Some(&'[') {
+        // Other sequences are intermediate characters followed by a final one, e.g. `ESC ( B`
+        while chars.next_if(|c| ('\x20'..='\x2f').contains(c)).is_some() {}
+        chars.next();
+        return None;
+    }
+    chars.next();
+    let mut params = String::new();
+    for c in chars.by_ref() {
+        if ('\x40'..='\x7e').contains(&c) {
+            return Some((params, c));
+        }
+        params.push(c);
+    }
+    None
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Converts SGR colour and style sequences into styled HTML spans
+fn ansi_to_html(text: &str) -> String {
+    let mut html = String::new();
+    let mut style = SgrStyle::default();
+    let mut span_open = false;
+    let mut chunk = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            chunk.push(c);
+            continue;
+        }
+        let Some((params, 'm')) = read_escape_sequence(&mut chars) else {
+            continue;
+        };
+        html.push_str(&escape_html(&chunk));
+        chunk.clear();
+        if span_open {
+            html.push_str("</span>");
+            span_open = false;
+        }
+        style.apply(&params);
+        let css = style.to_css();
+        if !css.is_empty() {
+            html.push_str(&format!("<span style=\"{css}\">"));
+            span_open = true;
+        }
+    }
+    html.push_str(&escape_html(&chunk));
+    if span_open {
+        html.push_str("</span>");
+    }
+    html
+}
+
+#[derive(Default)]
+struct SgrStyle {
+    foreground: Option<String>,
+    background: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+const ANSI_COLORS: [&str; 16] = [
+    "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+    "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+];
+
+impl SgrStyle {
+    fn apply(&mut self, params: &str) {
+        let codes: Vec<u16> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+        let mut i = 0;
+        while i < codes.len() {
+            match codes[i] {
+                0 => *self = SgrStyle::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                code @ 30..=37 => {
+                    self.foreground = Some(ANSI_COLORS[usize::from(code - 30)].into())
+                }
+                code @ 90..=97 => {
+                    self.foreground = Some(ANSI_COLORS[usize::from(code - 90 + 8)].into())
+                }
+                code @ 40..=47 => {
+                    self.background = Some(ANSI_COLORS[usize::from(code - 40)].into())
+                }
+                code @ 100..=107 => {
+                    self.background = Some(ANSI_COLORS[usize::from(code - 100 + 8)].into())
+                }
+                39 => self.foreground = None,
+                49 => self.background = None,
+                code @ (38 | 48) => {
+                    let (color, used) = extended_color(&codes[i + 1..]);
+                    if code == 38 {
+                        self.foreground = color;
+                    } else {
+                        self.background = color;
+                    }
+                    i += used;
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+
+    fn to_css(&self) -> String {
+        let mut css = vec![];
+        if let Some(foreground) = &self.foreground {
+            css.push(format!("color:{foreground}"));
+        }
+        if let Some(background) = &self.background {
+            css.push(format!("background:{background}"));
+        }
+        if self.bold {
+            css.push("font-weight:bold".into());
+        }
+        if self.italic {
+            css.push("font-style:italic".into());
+        }
+        if self.underline {
+            css.push("text-decoration:underline".into());
+        }
+        css.join(";")
+    }
+}
+
+/// Parses the arguments of a `38`/`48` code, returning the colour and the count of used codes
+fn extended_color(codes: &[u16]) -> (Option<String>, usize) {
+    match codes {
+        [5, index, ..] => (Some(color_256(*index)), 2),
+        [2, r, g, b, ..] => (Some(format!("#{:02x}{:02x}{:02x}", r, g, b)), 4),
+        _ => (None, codes.len()),
+    }
+}
+
+/// Converts an index of the xterm 256 colour palette to a CSS colour
+fn color_256(index: u16) -> String {
+    match index {
+        0..=15 => ANSI_COLORS[usize::from(index)].into(),
+        16..=231 => {
+            let index = index - 16;
+            let level = |v: u16| if v == 0 { 0 } else { v * 40 + 55 };
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                level(index / 36),
+                level((index / 6) % 6),
+                level(index % 6)
+            )
+        }
+        _ => {
+            let gray = (index.min(255) - 232) * 10 + 8;
+            format!("#{:02x}{:02x}{:02x}", gray, gray, gray)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_become_spans() {
+        assert_eq!(
+            ansi_to_html("a \x1b[31mred\x1b[0m b"),
+            "a <span style=\"color:#cd3131\">red</span> b"
+        );
+        assert_eq!(
+            ansi_to_html("\x1b[1;38;5;196;48;2;1;2;3mx"),
+            "<span style=\"color:#ff0000;background:#010203;font-weight:bold\">x</span>"
+        );
+        assert_eq!(
+            ansi_to_html("\x1b[92mgreen"),
+            "<span style=\"color:#23d18b\">green</span>"
+        );
+    }
+
+    #[test]
+    fn reset_closes_the_span() {
+        assert_eq!(
+            ansi_to_html("\x1b[4mu\x1b[24mplain\x1b[mstill"),
+            "<span style=\"text-decoration:underline\">u</span>plainstill"
+        );
+        assert_eq!(
+            ansi_to_html("\x1b[31;1mx\x1b[39my"),
+            "<span style=\"color:#cd3131;font-weight:bold\">x</span><span style=\"font-weight:bold\">y</span>"
+        );
+    }
+
+    #[test]
+    fn other_escape_sequences_are_dropped() {
+        assert_eq!(ansi_to_html("\x1b[2Ka\x1b[1Ab\x1b(Bc<d>"), "abc&lt;d&gt;");
+        assert_eq!(
+            strip_ansi("\x1b[1;31mred\x1b[0m \x1b[2K\x1b=done"),
+            "red done"
+        );
+        assert_eq!(strip_ansi("cut \x1b[3"), "cut ");
+    }
+}
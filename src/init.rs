@@ -0,0 +1,398 @@
+use crate::config;
+
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{self, BufRead, Write},
+    path::{Component, Path, PathBuf},
+};
+
+/// A command found in the project that can become a runner
+struct Entry {
+    /// Where the entry was found, used as the group name
+    source: &'static str,
+    name: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+}
+
+/// Name parts of scripts that usually keep running, those are started with the app
+const LONG_RUNNING: [&str; 6] = ["dev", "start", "serve", "watch", "web", "worker"];
+
+/// Scans the project in the working directory and writes a starter config with the entries the
+/// user picks, returning the exit code
+pub fn run(config_file: &str) -> i32 {
+    let mut entries = vec![];
+    entries.extend(package_scripts(Path::new("package.json")));
+    for makefile in ["GNUmakefile", "makefile", "Makefile"] {
+        if Path::new(makefile).exists() {
+            entries.extend(make_targets(Path::new(makefile)));
+            break;
+        }
+    }
+    entries.extend(cargo_aliases(Path::new(".cargo/config.toml")));
+    entries.extend(procfile_processes(Path::new("Procfile")));
+
+    if entries.is_empty() {
+        eprintln!(
+            "No package.json scripts, Makefile targets, .cargo/config.toml aliases or Procfile found"
+        );
+        return 1;
+    }
+
+    let mut source = "";
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.source != source {
+            source = entry.source;
+            println!("{source}");
+        }
+        println!(
+            "  {:>3}  {:<24} {}",
+            index + 1,
+            entry.name,
+            entry.args.join(" ")
+        );
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut ask = |question: &str| -> Option<String> {
+        print!("{question}");
+        io::stdout().flush().ok()?;
+        lines.next()?.ok()
+    };
+
+    let chosen = loop {
+        let Some(answer) = ask("Runners to add, e.g. `1,3-5`, or press enter to add all of them: ")
+        else {
+            return 1;
+        };
+        match parse_selection(&answer, entries.len()) {
+            Ok(chosen) => break chosen,
+            Err(err) => eprintln!("{err}"),
+        }
+    };
+
+    if Path::new(config_file).exists() {
+        let answer = ask(&format!(
+            "{config_file} already exists, overwrite it? [y/N] "
+        ));
+        if !answer.is_some_and(|a| a.trim().eq_ignore_ascii_case("y")) {
+            eprintln!("Nothing written");
+            return 1;
+        }
+    }
+
+    let chosen: Vec<&Entry> = chosen.into_iter().map(|index| &entries[index]).collect();
+    let contents = format!(
+        "# Generated by `control-panel init`, validate changes with `control-panel check`\n{}",
+        generate(&chosen, &project_dir(Path::new(config_file)))
+    );
+    match fs::write(config_file, contents) {
+        Ok(()) => {
+            println!("Wrote {} runners to {config_file}", chosen.len());
+            0
+        }
+        Err(err) => {
+            eprintln!("Cannot write {config_file}: {err}");
+            1
+        }
+    }
+}
+
+/// Indices of the entries in a selection like `1,3-5`, all entries when it is empty
+fn parse_selection(answer: &str, count: usize) -> Result<Vec<usize>, String> {
+    let answer = answer.trim();
+    if answer.is_empty() || answer == "all" {
+        return Ok((0..count).collect());
+    }
+
+    let mut chosen = vec![];
+    for part in answer.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let number = |text: &str| {
+            text.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|n| (1..=count).contains(n))
+                .ok_or_else(|| format!("`{part}` is not a number or range between 1 and {count}"))
+        };
+        let (first, last) = (number(first)?, number(last)?);
+        if first > last {
+            return Err(format!(
+                "`{part}` is a reversed range, write it as `{last}-{first}`"
+            ));
+        }
+        for index in first..=last {
+            if !chosen.contains(&(index - 1)) {
+                chosen.push(index - 1);
+            }
+        }
+    }
+    if chosen.is_empty() {
+        return Err("Choose at least one runner".into());
+    }
+    Ok(chosen)
+}
+
+/// The working directory as seen from the directory of the config file, as relative paths in the
+/// config are resolved against that directory. It is absolute when there is no relative path
+fn project_dir(config_file: &Path) -> String {
+    let absolute = |path: &Path| {
+        fs::canonicalize(path)
+            .or_else(|_| std::path::absolute(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let Ok(cwd) = env::current_dir().map(|cwd| absolute(&cwd)) else {
+        return ".".into();
+    };
+    let config_dir = absolute(config_file.parent().unwrap_or(Path::new("")));
+    relative_path(&config_dir, &cwd)
+}
+
+/// Path of `to` relative to `from`, both absolute
+fn relative_path(from: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    // Only the root in common, e.g. different drives, so the path stays absolute
+    if common <= 1 && from.len() > 1 {
+        return to.iter().collect::<PathBuf>().display().to_string();
+    }
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    path.extend(&to[common..]);
+    if path.as_os_str().is_empty() {
+        ".".into()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Config with a group per source and a runner per entry in `dir`. Entries that look long
+/// running start with the app, the others are started by hand
+fn generate(entries: &[&Entry], dir: &str) -> String {
+    let mut groups: Vec<(&str, Vec<Value>)> = vec![];
+    let mut names = HashSet::new();
+    for entry in entries {
+        // Runner names have to be unique, so a name that is taken gets the source in front
+        let name = if names.contains(&entry.name) {
+            format!("{} {}", entry.source, entry.name)
+        } else {
+            entry.name.clone()
+        };
+        names.insert(name.clone());
+
+        let mut runner = Mapping::new();
+        runner.insert("name".into(), name.into());
+        runner.insert("dir".into(), dir.into());
+        // `${` would be expanded as a config variable instead of by the command
+        let args: Vec<String> = entry.args.iter().map(|a| a.replace("${", "$${")).collect();
+        runner.insert("args".into(), args.into());
+        if !entry.env.is_empty() {
+            let env: Mapping = entry
+                .env
+                .iter()
+                .map(|(key, value)| (key.as_str().into(), value.replace("${", "$${").into()))
+                .collect();
+            runner.insert("env".into(), Value::Mapping(env));
+        }
+        let long_running = entry.source == "Procfile"
+            || LONG_RUNNING
+                .iter()
+                .any(|word| entry.name.to_lowercase().contains(word));
+        if !long_running {
+            runner.insert("auto_start".into(), false.into());
+        }
+
+        match groups
+            .iter_mut()
+            .find(|(source, _)| *source == entry.source)
+        {
+            Some((_, runners)) => runners.push(Value::Mapping(runner)),
+            None => groups.push((entry.source, vec![Value::Mapping(runner)])),
+        }
+    }
+
+    let groups: Vec<Value> = groups
+        .into_iter()
+        .map(|(source, runners)| {
+            let mut group = Mapping::new();
+            group.insert("name".into(), source.into());
+            group.insert("runners".into(), Value::Sequence(runners));
+            Value::Mapping(group)
+        })
+        .collect();
+    let mut root = Mapping::new();
+    root.insert("groups".into(), Value::Sequence(groups));
+    serde_yaml::to_string(&root).unwrap_or_default()
+}
+
+/// Scripts of a package.json, run with the package manager whose lock file is present
+fn package_scripts(file: &Path) -> Vec<Entry> {
+    let Ok(contents) = fs::read_to_string(file) else {
+        return vec![];
+    };
+    let Ok(package) = serde_json::from_str::<serde_json::Value>(&contents) else {
+        eprintln!("Skipping {}, it is not valid JSON", file.display());
+        return vec![];
+    };
+    let manager = [
+        ("pnpm-lock.yaml", "pnpm"),
+        ("yarn.lock", "yarn"),
+        ("bun.lockb", "bun"),
+        ("bun.lock", "bun"),
+    ]
+    .iter()
+    .find(|(lock, _)| Path::new(lock).exists())
+    .map_or("npm", |(_, manager)| manager);
+
+    package
+        .get("scripts")
+        .and_then(serde_json::Value::as_object)
+        .map(|scripts| {
+            scripts
+                .keys()
+                .map(|script| Entry {
+                    source: "package.json",
+                    name: script.clone(),
+                    args: vec![manager.into(), "run".into(), script.clone()],
+                    env: vec![],
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Explicit targets of a Makefile, leaving out special targets, pattern rules and variables
+fn make_targets(file: &Path) -> Vec<Entry> {
+    let Ok(contents) = fs::read_to_string(file) else {
+        return vec![];
+    };
+    let mut targets: Vec<String> = vec![];
+    for line in contents.lines() {
+        // Recipes are indented with tabs
+        if line.starts_with('\t') || line.starts_with('#') {
+            continue;
+        }
+        let Some((names, rest)) = line.split_once(':') else {
+            continue;
+        };
+        // Variable assignments like `A := b` or `A ::= b`
+        if rest.trim_start_matches(':').starts_with('=') || names.contains('=') {
+            continue;
+        }
+        for name in names.split_whitespace() {
+            let is_target = !name.starts_with('.')
+                && !name.contains(['%', '$', '/'])
+                && !targets.iter().any(|t| t == name);
+            if is_target {
+                targets.push(name.to_string());
+            }
+        }
+    }
+
+    targets
+        .into_iter()
+        .map(|target| Entry {
+            source: "make",
+            args: vec!["make".into(), target.clone()],
+            env: vec![],
+            name: target,
+        })
+        .collect()
+}
+
+/// Aliases under `[alias]` in the cargo config
+fn cargo_aliases(file: &Path) -> Vec<Entry> {
+    let Ok(contents) = fs::read_to_string(file) else {
+        return vec![];
+    };
+    let Ok(document) = toml_edit::Document::parse(contents.as_str()) else {
+        eprintln!("Skipping {}, it is not valid TOML", file.display());
+        return vec![];
+    };
+    document
+        .get("alias")
+        .and_then(|aliases| aliases.as_table_like())
+        .map(|aliases| {
+            aliases
+                .iter()
+                .map(|(alias, _)| Entry {
+                    source: "cargo",
+                    name: alias.to_string(),
+                    args: vec!["cargo".into(), alias.to_string()],
+                    env: vec![],
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Processes of a Procfile, run with `sh -c` like foreman does and with the same environment
+/// as when the Procfile is loaded directly
+fn procfile_processes(file: &Path) -> Vec<Entry> {
+    let Ok(contents) = fs::read_to_string(file) else {
+        return vec![];
+    };
+    match config::parse_procfile(file, &contents) {
+        Ok(processes) => processes
+            .into_iter()
+            .map(|process| Entry {
+                source: "Procfile",
+                name: process.name,
+                args: vec!["sh".into(), "-c".into(), process.command],
+                env: process.env,
+            })
+            .collect(),
+        Err(errors) => {
+            for err in errors {
+                eprintln!("{err}");
+            }
+            eprintln!("Skipping {}", file.display());
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_selections() {
+        assert_eq!(parse_selection("", 3).unwrap(), vec![0, 1, 2]);
+        assert_eq!(parse_selection("all", 2).unwrap(), vec![0, 1]);
+        assert_eq!(parse_selection("3, 1-2, 2", 4).unwrap(), vec![2, 0, 1]);
+        assert_eq!(parse_selection("2-2", 4).unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn rejects_bad_selections() {
+        assert_eq!(
+            parse_selection("2-1", 3).unwrap_err(),
+            "`2-1` is a reversed range, write it as `1-2`"
+        );
+        assert!(parse_selection("4", 3).is_err());
+        assert!(parse_selection("0", 3).is_err());
+        assert!(parse_selection("a-b", 3).is_err());
+        assert_eq!(
+            parse_selection(",", 3).unwrap_err(),
+            "Choose at least one runner"
+        );
+    }
+
+    #[test]
+    fn project_dir_is_relative_to_the_config() {
+        let relative = |from: &str, to: &str| relative_path(Path::new(from), Path::new(to));
+        assert_eq!(relative("/work/app", "/work/app"), ".");
+        assert_eq!(relative("/work/app/out", "/work/app"), "..");
+        assert_eq!(relative("/work/app/config", "/work/app/web"), "../web");
+        assert_eq!(relative("/work", "/work/app"), "app");
+        assert_eq!(relative("/etc/control-panel", "/work/app"), "/work/app");
+        assert_eq!(relative("/", "/work/app"), "work/app");
+    }
+}
@@ -0,0 +1,578 @@
+use crate::models::KeyBindingCfg;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, fmt};
+
+/// Everything that can be bound to a key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    Restart,
+    Stop,
+    NextPane,
+    PreviousPane,
+    ToggleTimestamps,
+    Zoom,
+    ToggleDebug,
+    DebugScrollUp,
+    DebugScrollDown,
+    DebugLevel,
+    DebugFilter,
+    CycleLayout,
+    NextGroup,
+    PreviousGroup,
+    Group(usize),
+    GrowPane,
+    ShrinkPane,
+    GrowRow,
+    ShrinkRow,
+    Help,
+    Cancel,
+    ScrollDown,
+    ScrollUp,
+    ScrollRight,
+    ScrollLeft,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    ToggleFollow,
+    ToggleWrap,
+    ToggleMetrics,
+    Details,
+    ReloadConfig,
+    ClearPane,
+    PreviousRun,
+    NextRun,
+    Select,
+    Yank,
+    YankWithTimestamps,
+    Export,
+}
+
+/// Config name, description and default keys of every action, in the order shown in the help
+const ACTIONS: &[(Action, &str, &str, &[&str])] = &[
+    (Action::NextPane, "next_pane", "Next pane", &["n"]),
+    (
+        Action::PreviousPane,
+        "previous_pane",
+        "Previous pane",
+        &["p"],
+    ),
+    (Action::Zoom, "zoom", "Zoom out/in pane", &["z"]),
+    (
+        Action::ToggleTimestamps,
+        "toggle_timestamps",
+        "Toggle timestamps",
+        &["t"],
+    ),
+    (
+        Action::Stop,
+        "stop",
+        "Stop process in the active pane",
+        &["s"],
+    ),
+    (
+        Action::Restart,
+        "restart",
+        "Restart process in the active pane",
+        &["r"],
+    ),
+    (Action::Quit, "quit", "Quit app", &["q"]),
+    (
+        Action::ToggleDebug,
+        "toggle_debug",
+        "Toggle debug window",
+        &["d"],
+    ),
+    (
+        Action::DebugScrollUp,
+        "debug_scroll_up",
+        "Scroll the debug window up",
+        &["K"],
+    ),
+    (
+        Action::DebugScrollDown,
+        "debug_scroll_down",
+        "Scroll the debug window down",
+        &["J"],
+    ),
+    (
+        Action::DebugLevel,
+        "debug_level",
+        "Cycle the lowest level shown in the debug window",
+        &["L"],
+    ),
+    (
+        Action::DebugFilter,
+        "debug_filter",
+        "Filter the debug window by text",
+        &["/"],
+    ),
+    (
+        Action::GrowPane,
+        "grow_pane",
+        "Grow the active pane",
+        &["+"],
+    ),
+    (
+        Action::ShrinkPane,
+        "shrink_pane",
+        "Shrink the active pane",
+        &["-"],
+    ),
+    (
+        Action::GrowRow,
+        "grow_row",
+        "Grow the row of the active pane",
+        &["}"],
+    ),
+    (
+        Action::ShrinkRow,
+        "shrink_row",
+        "Shrink the row of the active pane",
+        &["{"],
+    ),
+    (
+        Action::CycleLayout,
+        "cycle_layout",
+        "Cycle between the grid, sidebar and tabs layouts",
+        &["b"],
+    ),
+    (Action::NextGroup, "next_group", "Next group", &["tab"]),
+    (
+        Action::PreviousGroup,
+        "previous_group",
+        "Previous group",
+        &["shift+tab"],
+    ),
+    (Action::Group(0), "group_1", "Jump to group 1", &["1"]),
+    (Action::Group(1), "group_2", "Jump to group 2", &["2"]),
+    (Action::Group(2), "group_3", "Jump to group 3", &["3"]),
+    (Action::Group(3), "group_4", "Jump to group 4", &["4"]),
+    (Action::Group(4), "group_5", "Jump to group 5", &["5"]),
+    (Action::Group(5), "group_6", "Jump to group 6", &["6"]),
+    (Action::Group(6), "group_7", "Jump to group 7", &["7"]),
+    (Action::Group(7), "group_8", "Jump to group 8", &["8"]),
+    (Action::Group(8), "group_9", "Jump to group 9", &["9"]),
+    (
+        Action::Select,
+        "select",
+        "Start/stop selecting lines in the active pane",
+        &["v"],
+    ),
+    (Action::Yank, "yank", "Copy the selected lines", &["y"]),
+    (
+        Action::YankWithTimestamps,
+        "yank_with_timestamps",
+        "Copy the selected lines with timestamps",
+        &["Y"],
+    ),
+    (
+        Action::Export,
+        "export",
+        "Export the active pane or the selected lines to a file",
+        &["e"],
+    ),
+    (
+        Action::ScrollDown,
+        "scroll_down",
+        "Scroll the active pane down",
+        &["j", "down"],
+    ),
+    (
+        Action::ScrollUp,
+        "scroll_up",
+        "Scroll the active pane up",
+        &["k", "up"],
+    ),
+    (
+        Action::ScrollRight,
+        "scroll_right",
+        "Scroll the active pane right",
+        &["l", "right"],
+    ),
+    (
+        Action::ScrollLeft,
+        "scroll_left",
+        "Scroll the active pane left",
+        &["h", "left"],
+    ),
+    (
+        Action::PageDown,
+        "page_down",
+        "Scroll a page down",
+        &["pagedown"],
+    ),
+    (Action::PageUp, "page_up", "Scroll a page up", &["pageup"]),
+    (
+        Action::HalfPageDown,
+        "half_page_down",
+        "Scroll half a page down",
+        &["ctrl+d"],
+    ),
+    (
+        Action::HalfPageUp,
+        "half_page_up",
+        "Scroll half a page up",
+        &["ctrl+u"],
+    ),
+    (Action::Top, "top", "Jump to the top", &["g", "home"]),
+    (
+        Action::Bottom,
+        "bottom",
+        "Jump to the bottom",
+        &["G", "end"],
+    ),
+    (
+        Action::ToggleFollow,
+        "toggle_follow",
+        "Toggle following new output",
+        &["f"],
+    ),
+    (
+        Action::ToggleWrap,
+        "toggle_wrap",
+        "Toggle line wrapping in the active pane",
+        &["w"],
+    ),
+    (
+        Action::ToggleMetrics,
+        "toggle_metrics",
+        "Toggle CPU and memory usage of the panes",
+        &["m"],
+    ),
+    (
+        Action::ClearPane,
+        "clear_pane",
+        "Clear the output of the active pane",
+        &["c"],
+    ),
+    (
+        Action::PreviousRun,
+        "previous_run",
+        "Show only the current run, then earlier runs",
+        &["["],
+    ),
+    (
+        Action::NextRun,
+        "next_run",
+        "Show only a later run, or all runs after the last one",
+        &["]"],
+    ),
+    (
+        Action::Details,
+        "details",
+        "Show details of the active runner",
+        &["i"],
+    ),
+    (
+        Action::ReloadConfig,
+        "reload_config",
+        "Reload the config file",
+        &["R"],
+    ),
+    (Action::Help, "help", "Show key-bindings", &["?"]),
+    (
+        Action::Cancel,
+        "cancel",
+        "Close popups and cancel the selection",
+        &["esc"],
+    ),
+];
+
+/// A key together with its modifiers, e.g. `ctrl+d`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    /// Shift is already part of upper case characters and of back tab, so it is dropped to make
+    /// `G` and `shift+g` the same chord
+    fn normalized(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeyChord { code, modifiers }
+    }
+
+    /// Parses chords like `q`, `G`, `ctrl+d`, `shift+tab`, `pagedown` or `alt+f1`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (modifier_names, key_name) = if text == "+" {
+            ("", "+")
+        } else if let Some(modifier_names) = text.strip_suffix("++") {
+            (modifier_names, "+")
+        } else {
+            text.rsplit_once('+').unwrap_or(("", text))
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_names.split('+').filter(|n| !n.is_empty()) {
+            modifiers |= match name.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier `{name}` in `{text}`")),
+            };
+        }
+
+        let mut chars = key_name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key_name.to_lowercase().as_str() {
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" | "return" => KeyCode::Enter,
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "space" => KeyCode::Char(' '),
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{key_name}` in `{text}`")),
+                },
+            },
+        };
+
+        Ok(KeyChord::normalized(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{code}"),
+        }
+    }
+}
+
+/// Keys bound to every action
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<KeyChord>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: ACTIONS
+                .iter()
+                .map(|(action, _, _, keys)| {
+                    let chords = keys
+                        .iter()
+                        .map(|k| KeyChord::parse(k).expect("Invalid default key binding"))
+                        .collect();
+                    (*action, chords)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults with the actions in the config rebound, every problem
+    /// in the config is returned at once together with the action it is about
+    pub fn from_config(
+        keys: &HashMap<String, KeyBindingCfg>,
+    ) -> Result<Self, Vec<(String, String)>> {
+        let mut keymap = Keymap::default();
+        let mut errors = vec![];
+
+        for (name, binding) in keys.iter() {
+            let Some(position) = ACTIONS.iter().position(|(_, n, _, _)| n == name) else {
+                errors.push((name.clone(), format!("unknown action `{name}`")));
+                continue;
+            };
+            let mut chords = vec![];
+            for key in binding.keys() {
+                match KeyChord::parse(key) {
+                    Ok(chord) => chords.push(chord),
+                    Err(err) => errors.push((name.clone(), format!("{name}: {err}"))),
+                }
+            }
+            if binding.keys().is_empty() && ACTIONS[position].0 == Action::Quit {
+                errors.push((name.clone(), format!("{name}: at least one key is needed")));
+            }
+            keymap.bindings[position].1 = chords;
+        }
+
+        // A key would only ever trigger the first of its actions, the others silently lose it
+        let mut bound: Vec<(KeyChord, Vec<&str>)> = vec![];
+        for ((_, chords), (_, name, _, _)) in keymap.bindings.iter().zip(ACTIONS) {
+            for chord in chords {
+                match bound.iter_mut().find(|(c, _)| c == chord) {
+                    Some((_, names)) if !names.contains(name) => names.push(name),
+                    Some(_) => {}
+                    None => bound.push((*chord, vec![name])),
+                }
+            }
+        }
+        for (chord, names) in bound.iter().filter(|(_, names)| names.len() > 1) {
+            let (last, rest) = names.split_last().unwrap();
+            // Reported at an action that is rebound in the config, the others keep their defaults
+            let rebound = names.iter().rev().find(|name| keys.contains_key(**name));
+            errors.push((
+                rebound.unwrap_or(last).to_string(),
+                format!("`{chord}` is bound to both {} and {last}", rest.join(", ")),
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(keymap)
+        } else {
+            errors.sort();
+            Err(errors)
+        }
+    }
+
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        let chord = KeyChord::normalized(key.code, key.modifiers);
+        self.bindings
+            .iter()
+            .find(|(_, chords)| chords.contains(&chord))
+            .map(|(action, _)| *action)
+    }
+
+    /// Keys of an action formatted for display, e.g. `<j>/<Down>`
+    pub fn keys_for(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, chords)| {
+                chords
+                    .iter()
+                    .map(|c| format!("<{c}>"))
+                    .collect::<Vec<String>>()
+                    .join("/")
+            })
+            .unwrap_or_default()
+    }
+
+    /// Bound keys and description of every action that has at least one key
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        ACTIONS
+            .iter()
+            .map(|(action, _, description, _)| (self.keys_for(*action), *description))
+            .filter(|(keys, _)| !keys.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord { code, modifiers }
+    }
+
+    fn keys(bindings: &[(&str, &[&str])]) -> HashMap<String, KeyBindingCfg> {
+        bindings
+            .iter()
+            .map(|(name, keys)| {
+                let keys = keys.iter().map(|k| k.to_string()).collect();
+                (name.to_string(), KeyBindingCfg::Many(keys))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_chords() {
+        let parse = |text| KeyChord::parse(text).unwrap();
+        assert_eq!(parse("q"), chord(KeyCode::Char('q'), KeyModifiers::NONE));
+        assert_eq!(
+            parse("ctrl+d"),
+            chord(KeyCode::Char('d'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(parse("Control+Alt+x"), parse("alt+ctrl+x"));
+        assert_eq!(parse("+"), chord(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(
+            parse("ctrl++"),
+            chord(KeyCode::Char('+'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(parse("pgdn"), parse("pagedown"));
+        assert_eq!(parse("alt+f1"), chord(KeyCode::F(1), KeyModifiers::ALT));
+        assert_eq!(
+            parse("space"),
+            chord(KeyCode::Char(' '), KeyModifiers::NONE)
+        );
+    }
+
+    #[test]
+    fn shift_is_part_of_the_key() {
+        let parse = |text| KeyChord::parse(text).unwrap();
+        assert_eq!(parse("shift+g"), parse("G"));
+        assert_eq!(parse("shift+tab"), parse("backtab"));
+        assert_eq!(parse("shift+up"), chord(KeyCode::Up, KeyModifiers::SHIFT));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert!(KeyChord::parse("hyper+a").is_err());
+        assert!(KeyChord::parse("f13").is_err());
+        assert!(KeyChord::parse("ctrl+nope").is_err());
+        assert!(KeyChord::parse("").is_err());
+    }
+
+    #[test]
+    fn default_keys_do_not_conflict() {
+        assert!(Keymap::from_config(&HashMap::new()).is_ok());
+    }
+
+    #[test]
+    fn rejects_keys_bound_to_two_actions() {
+        let errors = Keymap::from_config(&keys(&[("quit", &["r"])]))
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors,
+            vec![(
+                "quit".into(),
+                "`r` is bound to both restart and quit".into()
+            )]
+        );
+
+        let keymap = Keymap::from_config(&keys(&[("quit", &["r"]), ("restart", &["x"])]));
+        assert!(keymap.is_ok());
+    }
+
+    #[test]
+    fn quit_needs_a_key() {
+        let errors = Keymap::from_config(&keys(&[("quit", &[])])).err().unwrap();
+        assert_eq!(
+            errors,
+            vec![("quit".into(), "quit: at least one key is needed".into())]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use crate::models::LimitsCfg;
+
+use std::{io, os::unix::process::CommandExt, process::Command};
+
+/// Makes the command set the configured limits in the child process before it executes
+pub fn apply(cmd: &mut Command, limits: &LimitsCfg) {
+    let mut rlimits = vec![];
+    if let Some(size) = limits.address_space {
+        rlimits.push((libc::RLIMIT_AS, size.0));
+    }
+    if let Some(seconds) = limits.cpu_seconds {
+        rlimits.push((libc::RLIMIT_CPU, seconds));
+    }
+    if let Some(files) = limits.open_files {
+        rlimits.push((libc::RLIMIT_NOFILE, files));
+    }
+    if let Some(size) = limits.core_size {
+        rlimits.push((libc::RLIMIT_CORE, size.0));
+    }
+    if rlimits.is_empty() {
+        return;
+    }
+
+    // SAFETY: the closure runs between fork and exec and only calls setrlimit, which is
+    // async-signal-safe, on data that was prepared before the fork
+    unsafe {
+        cmd.pre_exec(move || {
+            for (resource, value) in rlimits.iter() {
+                let mut limit = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::getrlimit(*resource, &mut limit) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Raising the hard limit needs privileges, so the limit is capped at the current one
+                let value = (*value as libc::rlim_t).min(limit.rlim_max);
+                limit.rlim_cur = value;
+                limit.rlim_max = value;
+                if libc::setrlimit(*resource, &limit) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
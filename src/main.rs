@@ -1,16 +1,39 @@
+mod cli;
+use cli::*;
+
+mod clipboard;
+
+mod config;
+
+mod export;
+
+mod init;
+
+mod keys;
+
+mod limits;
+
+mod metrics;
+
+mod reload;
+
+mod theme;
+
 mod models;
-use crossbeam_channel::{unbounded, Receiver, Sender};
 use models::*;
 
 mod ui;
 use ui::*;
 
+mod utils;
+
 mod worker;
 use worker::*;
 
+use clap::{CommandFactory, Parser};
 use std::{
-    env,
     io::{self, stdout},
+    process,
     sync::{Arc, Mutex},
     thread,
     time::Duration,
@@ -19,7 +42,7 @@ use std::{
 use ratatui::{
     backend::CrosstermBackend,
     crossterm::{
-        event::DisableMouseCapture,
+        event::{DisableMouseCapture, EnableMouseCapture},
         execute,
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
         ExecutableCommand,
@@ -30,50 +53,125 @@ use ratatui::{
 use std::fs;
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let config_file = if args.len() > 1 {
-        args[1].clone()
-    } else {
-        "./config.yml".to_string()
+    let cli = Cli::parse();
+    match cli.command {
+        None => run(cli.run),
+        Some(Command::Run(args)) => run(args),
+        Some(Command::Check(args)) => process::exit(check_config(&args)),
+        Some(Command::List { config, filter }) => process::exit(list_runners(&config, &filter)),
+        Some(Command::Init { file }) => process::exit(init::run(&file)),
+        Some(Command::Version) => {
+            println!("control-panel {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "control-panel", &mut stdout());
+            Ok(())
+        }
+        Some(Command::Schema) => {
+            print!("{}", config::schema());
+            Ok(())
+        }
+    }
+}
+
+/// Starts the runners of the config and shows the UI until it is quit
+fn run(args: RunArgs) -> io::Result<()> {
+    let (config_file, format) = args.config.file();
+    let overrides = args.overrides();
+    if args.print_config {
+        process::exit(print_config(&config_file, format, &overrides));
+    }
+
+    let cfg = match config::load_filtered(&config_file, format, &overrides) {
+        Ok(cfg) => cfg,
+        Err(errors) => {
+            for err in errors {
+                eprintln!("{err}");
+            }
+            process::exit(1);
+        }
     };
 
+    let signals = block_signals();
     let app = Arc::new(Mutex::new(App::default()));
+    app.lock().unwrap().config_path =
+        fs::canonicalize(&config_file).unwrap_or_else(|_| config_file.into());
+    app.lock().unwrap().config_files = cfg.files.clone();
+    app.lock().unwrap().config_format = format;
+    app.lock().unwrap().config_overrides = overrides;
+    app.lock().unwrap().layout = args.layout.unwrap_or(cfg.layout);
+    app.lock().unwrap().show_metrics = cfg.metrics.enabled;
+    app.lock().unwrap().keymap = match keys::Keymap::from_config(&cfg.keys) {
+        Ok(keymap) => keymap,
+        Err(errors) => {
+            for (_, err) in errors {
+                eprintln!("Invalid key binding: {err}");
+            }
+            process::exit(1);
+        }
+    };
+    app.lock().unwrap().theme = match theme::Theme::from_config(&cfg.theme) {
+        Ok(theme) => theme,
+        Err(errors) => {
+            for (_, err) in errors {
+                eprintln!("Invalid theme: {err}");
+            }
+            process::exit(1);
+        }
+    };
 
-    let contents = fs::read_to_string(config_file).unwrap();
-
-    let cfg: Cfg = serde_yaml::from_str(&contents.to_owned()).unwrap();
+    app.lock().unwrap().debug_record_level = cfg.debug.level;
+    if let Some(path) = &cfg.debug.file {
+        match fs::OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => app.lock().unwrap().debug_file = Some(file),
+            Err(err) => {
+                eprintln!("Invalid debug log file {path}: {err}");
+                process::exit(1);
+            }
+        }
+    }
 
-    for r_group in cfg.groups.iter() {
+    for (group_index, r_group) in cfg.groups.iter().enumerate() {
+        let mut app = app.lock().unwrap();
         let mut group: Vec<usize> = vec![];
         for r_cfg in r_group.runners.iter() {
-            let (r_tx, r_rx): (Sender<RunnerEvent>, Receiver<RunnerEvent>) = unbounded();
-            group.push(app.lock().unwrap().runners.len());
-            app.lock().unwrap().runners.push(Runner {
-                name: r_cfg.name.to_owned(),
-                args: r_cfg.args.clone(),
-                lines: Vec::new(),
-                vertical_scroll_size: 0,
-                vertical_scroll_position: 0,
-                // horizontal_scroll_size: 0,
-                // horizontal_scroll_state: ScrollbarState::new(0).position(0),
-                state: RunnerState::Ready,
-                should_restart: false,
-                tx: r_tx,
-                rx: r_rx,
-            });
-        }
-        app.lock().unwrap().groups.push(Group { runners: group });
+            group.push(app.runners.len());
+            let id = app.next_runner_id;
+            app.next_runner_id += 1;
+            app.runners.push(Runner::new(id, r_cfg));
+        }
+        app.groups.push(Group {
+            name: r_group.display_name(group_index),
+            runners: group,
+            size: r_group.size.max(1),
+            config_size: r_group.size,
+        });
     }
 
+    let signal_app = app.clone();
+    let signal_process = thread::spawn(move || quit_on_signal(signal_app, signals));
+
+    let mouse = cfg.mouse;
     let mut ui_app = app.clone();
     let ui_process = thread::spawn(move || {
         enable_raw_mode()?;
         stdout().execute(EnterAlternateScreen)?;
+        if mouse {
+            stdout().execute(EnableMouseCapture)?;
+        }
         let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
         let tick_rate = Duration::from_millis(250);
         //let mut u2 = ui_app.lock().unwrap();
         let res = run_app(&mut terminal, &mut ui_app, tick_rate);
+        // The UI also ends when the terminal fails, the runners still have to be stopped then
+        {
+            let mut app = ui_app.lock().unwrap();
+            if !app.should_exit {
+                quit(&mut app, "The UI ended, stopping all runners");
+            }
+        }
 
         // restore terminal
         disable_raw_mode()?;
@@ -87,23 +185,32 @@ fn main() -> io::Result<()> {
         res
     });
 
+    let metrics_app = app.clone();
+    let metrics_cfg = cfg.metrics.clone();
+    thread::spawn(move || metrics::start_sampler(metrics_app, metrics_cfg));
+
+    let reload_app = app.clone();
+    let reload_process = thread::spawn(move || reload::start_watcher(reload_app));
+
     let worker_app = app.clone();
     let woker_process = thread::spawn(move || {
         // Start the process
 
         let mut handlers = vec![];
 
-        let mut index = 0;
-        for runner_group in cfg.groups {
-            for runner_config in runner_group.runners {
-                let runner_app = worker_app.clone();
-                let runner_index = index.clone();
-                let handle =
-                    thread::spawn(move || start_worker(runner_app, runner_config, runner_index));
+        let runner_ids: Vec<usize> = worker_app
+            .lock()
+            .unwrap()
+            .runners
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        let runner_configs = cfg.groups.into_iter().flat_map(|g| g.runners);
+        for (runner_config, runner_id) in runner_configs.zip(runner_ids) {
+            let runner_app = worker_app.clone();
+            let handle = thread::spawn(move || start_worker(runner_app, runner_config, runner_id));
 
-                handlers.push(handle);
-                index += 1;
-            }
+            handlers.push(handle);
         }
 
         for handle in handlers {
@@ -111,13 +218,131 @@ fn main() -> io::Result<()> {
         }
     });
 
-    if let Err(err) = ui_process.join() {
+    if let Err(err) = woker_process.join() {
         println!("{err:?}");
     }
 
-    if let Err(err) = woker_process.join() {
+    // After a hangup the terminal is gone and polling it may never return, so the UI is not
+    // waited for
+    let hung_up =
+        signal_process.is_finished() && matches!(signal_process.join(), Ok(Some(libc::SIGHUP)));
+    if !hung_up {
+        if let Err(err) = ui_process.join() {
+            println!("{err:?}");
+        }
+    }
+
+    if let Err(err) = reload_process.join() {
         println!("{err:?}");
     }
 
     Ok(())
 }
+
+/// Blocks the signals that end the app in this thread and the threads started from it, so they
+/// are only received by `quit_on_signal`. Runners start with no signals blocked
+fn block_signals() -> libc::sigset_t {
+    // SAFETY: the set is initialized by sigemptyset before it is used
+    unsafe {
+        let mut signals: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        for signal in [libc::SIGHUP, libc::SIGTERM, libc::SIGINT] {
+            libc::sigaddset(&mut signals, signal);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut());
+        signals
+    }
+}
+
+/// Quits like the quit key when the terminal hangs up or the app is asked to stop, so the
+/// runners don't outlive the app. Returns the signal that was received
+fn quit_on_signal(app: Arc<Mutex<App>>, signals: libc::sigset_t) -> Option<libc::c_int> {
+    let mut signal = 0;
+    // SAFETY: sigwait only waits for one of the blocked signals and writes its number
+    if unsafe { libc::sigwait(&signals, &mut signal) } != 0 {
+        return None;
+    }
+    quit(
+        &mut app.lock().unwrap(),
+        &format!("Received signal {signal}, stopping all runners"),
+    );
+    Some(signal)
+}
+
+/// Validates the config file and prints the result, returning the exit code
+fn check_config(args: &ConfigArgs) -> i32 {
+    let (config_file, format) = args.file();
+    match config::load(&config_file, format) {
+        Ok(cfg) => {
+            let runners: usize = cfg.groups.iter().map(|g| g.runners.len()).sum();
+            let groups = cfg.groups.len();
+            let runner_plural = if runners == 1 { "" } else { "s" };
+            let group_plural = if groups == 1 { "" } else { "s" };
+            println!(
+                "{config_file}: ok, {runners} runner{runner_plural} in {groups} group{group_plural}"
+            );
+            0
+        }
+        Err(errors) => {
+            for err in errors.iter() {
+                eprintln!("{err}");
+            }
+            let plural = if errors.len() == 1 { "" } else { "s" };
+            eprintln!("{config_file}: {} error{plural}", errors.len());
+            1
+        }
+    }
+}
+
+/// Prints the config merged from all of its files, returning the exit code
+fn print_config(
+    config_file: &str,
+    format: Option<config::Format>,
+    overrides: &config::Overrides,
+) -> i32 {
+    match config::print_merged(config_file, format, overrides) {
+        Ok(merged) => {
+            print!("{merged}");
+            0
+        }
+        Err(errors) => {
+            for err in errors.iter() {
+                eprintln!("{err}");
+            }
+            1
+        }
+    }
+}
+
+/// Prints the groups and runners left after filtering, returning the exit code
+fn list_runners(args: &ConfigArgs, filter: &FilterArgs) -> i32 {
+    let (config_file, format) = args.file();
+    let overrides = filter.overrides(false);
+    let cfg = config::load_filtered(&config_file, format, &overrides);
+    match cfg {
+        Ok(cfg) => {
+            for (group_index, group) in cfg.groups.iter().enumerate() {
+                println!("{}", group.display_name(group_index));
+                for runner in group.runners.iter() {
+                    let manual = if runner.auto_start {
+                        ""
+                    } else {
+                        "  (started by hand)"
+                    };
+                    println!(
+                        "  {:<24} {}{manual}",
+                        runner.name,
+                        shell_words(&runner.args)
+                    );
+                }
+            }
+            0
+        }
+        Err(errors) => {
+            for err in errors.iter() {
+                eprintln!("{err}");
+            }
+            1
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::models::{App, EventType, LogLevel, MetricsCfg, RunTrigger, RunnerEvent};
+use crate::utils::log_debug;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Count of samples kept for the sparklines
+const HISTORY_LENGTH: usize = 10;
+
+/// Resource usage of a runner's process, optionally including all of its descendants
+#[derive(Debug, Clone)]
+pub struct ProcessMetrics {
+    pub pid: u32,
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub threads: u64,
+    pub open_files: u64,
+    pub cpu_history: VecDeque<f64>,
+    pub rss_history: VecDeque<u64>,
+    cpu_ticks: u64,
+    sampled_at: Instant,
+}
+
+/// Fields of `/proc/<pid>/stat` that are used for the metrics
+struct ProcStat {
+    ppid: u32,
+    cpu_ticks: u64,
+    threads: u64,
+    rss_pages: u64,
+}
+
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name can contain spaces and parentheses, so the fields are read after its end
+    let fields: Vec<&str> = stat.get(stat.rfind(')')? + 2..)?.split(' ').collect();
+    let field = |index: usize| fields.get(index - 3).and_then(|f| f.parse::<u64>().ok());
+
+    Some(ProcStat {
+        ppid: field(4)?.try_into().ok()?,
+        cpu_ticks: field(14)? + field(15)?,
+        threads: field(20)?,
+        rss_pages: field(24)?,
+    })
+}
+
+fn count_open_files(pid: u32) -> u64 {
+    fs::read_dir(format!("/proc/{pid}/fd")).map_or(0, |entries| entries.count() as u64)
+}
+
+/// Children of every running process, by parent pid
+fn process_children() -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    if let Ok(entries) = fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            if let Some(stat) = read_stat(pid) {
+                children.entry(stat.ppid).or_default().push(pid);
+            }
+        }
+    }
+
+    children
+}
+
+/// Finds the process and all of its descendants
+fn process_tree(root: u32, children: &HashMap<u32, Vec<u32>>) -> Vec<u32> {
+    let mut tree = vec![root];
+    let mut index = 0;
+    while index < tree.len() {
+        if let Some(pids) = children.get(&tree[index]) {
+            tree.extend(pids);
+        }
+        index += 1;
+    }
+    tree
+}
+
+/// Reads the current usage of the process, including its descendants when the children of all
+/// processes are given. The CPU usage is measured since the previous sample
+fn sample(
+    pid: u32,
+    children: Option<&HashMap<u32, Vec<u32>>>,
+    previous: Option<ProcessMetrics>,
+) -> Option<ProcessMetrics> {
+    let root = read_stat(pid)?;
+    let pids = match children {
+        Some(children) => process_tree(pid, children),
+        None => vec![pid],
+    };
+
+    let mut cpu_ticks = root.cpu_ticks;
+    let mut rss_pages = root.rss_pages;
+    let mut threads = root.threads;
+    let mut open_files = count_open_files(pid);
+    for child in pids.iter().skip(1) {
+        if let Some(stat) = read_stat(*child) {
+            cpu_ticks += stat.cpu_ticks;
+            rss_pages += stat.rss_pages;
+            threads += stat.threads;
+            open_files += count_open_files(*child);
+        }
+    }
+
+    let sampled_at = Instant::now();
+    let (cpu_percent, mut cpu_history, mut rss_history) = match previous {
+        Some(previous) if previous.pid == pid => {
+            let elapsed = (sampled_at - previous.sampled_at).as_secs_f64();
+            let ticks = cpu_ticks.saturating_sub(previous.cpu_ticks) as f64;
+            let cpu_percent = if elapsed > 0.0 {
+                ticks / clock_ticks_per_second() / elapsed * 100.0
+            } else {
+                0.0
+            };
+            (cpu_percent, previous.cpu_history, previous.rss_history)
+        }
+        _ => (0.0, VecDeque::new(), VecDeque::new()),
+    };
+
+    let rss_bytes = rss_pages * page_size();
+    cpu_history.push_back(cpu_percent);
+    rss_history.push_back(rss_bytes);
+    if cpu_history.len() > HISTORY_LENGTH {
+        cpu_history.pop_front();
+        rss_history.pop_front();
+    }
+
+    Some(ProcessMetrics {
+        pid,
+        cpu_percent,
+        rss_bytes,
+        threads,
+        open_files,
+        cpu_history,
+        rss_history,
+        cpu_ticks,
+        sampled_at,
+    })
+}
+
+fn clock_ticks_per_second() -> f64 {
+    // SAFETY: sysconf only reads a system configuration value
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f64
+    } else {
+        100.0
+    }
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf only reads a system configuration value
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 {
+        size as u64
+    } else {
+        4096
+    }
+}
+
+/// Samples the processes of all active runners in the background until the app exits. Only
+/// runners with a memory watchdog are sampled while the metrics are hidden
+pub fn start_sampler(app: Arc<Mutex<App>>, cfg: MetricsCfg) {
+    let interval = Duration::from_millis(cfg.interval_ms.max(100));
+    loop {
+        thread::sleep(interval);
+
+        let targets: Vec<(usize, Option<u32>, Option<ProcessMetrics>)> = {
+            let mut app = app.lock().unwrap();
+            if app.should_exit {
+                return;
+            }
+            let show_metrics = app.show_metrics;
+            app.runners
+                .iter_mut()
+                .map(|r| {
+                    let pid = r.pid.filter(|_| show_metrics || r.rss_watchdog.is_some());
+                    (r.id, pid, r.metrics.take())
+                })
+                .collect()
+        };
+
+        let children = (cfg.process_tree && targets.iter().any(|(_, pid, _)| pid.is_some()))
+            .then(process_children);
+        let samples: Vec<(usize, Option<ProcessMetrics>)> = targets
+            .into_iter()
+            .map(|(id, pid, previous)| {
+                (
+                    id,
+                    pid.and_then(|pid| sample(pid, children.as_ref(), previous)),
+                )
+            })
+            .collect();
+
+        let mut app = app.lock().unwrap();
+        let mut messages = vec![];
+        // Runners are matched by id, as a reload in the meantime can move or remove them
+        for (id, metrics) in samples {
+            let Some(runner) = app.runner_mut(id) else {
+                continue;
+            };
+            // A restart in the meantime makes the sample belong to the previous process
+            runner.metrics = metrics.filter(|m| Some(m.pid) == runner.pid);
+
+            let Some(metrics) = runner.metrics.as_ref() else {
+                continue;
+            };
+            if runner
+                .rss_watchdog
+                .is_some_and(|limit| metrics.rss_bytes > limit)
+            {
+                messages.push(format!(
+                    "{} restarted, memory usage {} went above the limit",
+                    runner.name,
+                    format_bytes(metrics.rss_bytes)
+                ));
+                runner.metrics = None;
+                let _ = runner.tx.send(RunnerEvent {
+                    event_type: EventType::Restart(RunTrigger::Watchdog),
+                });
+            }
+        }
+        for message in messages {
+            log_debug(&mut app, LogLevel::Warn, message.clone());
+            app.notify(message);
+        }
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `45.2M`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{value:.1}{}", UNITS[unit])
+    }
+}
+
+/// Draws the values as a line of block characters scaled to the largest value
+pub fn sparkline(values: impl Iterator<Item = f64> + Clone) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.clone().fold(0.0, f64::max);
+    values
+        .map(|v| {
+            if max <= 0.0 {
+                BARS[0]
+            } else {
+                BARS[((v / max) * (BARS.len() - 1) as f64).round() as usize]
+            }
+        })
+        .collect()
+}
@@ -1,18 +1,126 @@
-use chrono::{DateTime, Utc};
-use crossbeam_channel::{Receiver, Sender};
-use ratatui::text::Line;
-use serde::Deserialize;
+use chrono::{DateTime, Local, Utc};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use ratatui::{
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap, fmt, fs::File, ops::Range, path::PathBuf, process::ExitStatus,
+    time::Instant,
+};
+
+use crate::config::{Format, Overrides};
+use crate::keys::Keymap;
+use crate::metrics::ProcessMetrics;
+use crate::theme::Theme;
 
 #[derive(Default, Clone)]
 pub struct StdLine {
     pub timestamp: DateTime<Utc>,
+    pub stream: Stream,
     pub content: String,
 }
 
+/// Severity of a debug log message
+#[derive(Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[default]
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+
+    /// The next higher level, wrapping around to the lowest one
+    pub fn next(&self) -> Self {
+        match self {
+            LogLevel::Debug => LogLevel::Info,
+            LogLevel::Info => LogLevel::Warn,
+            LogLevel::Warn => LogLevel::Error,
+            LogLevel::Error => LogLevel::Debug,
+        }
+    }
+}
+
+/// A message of the internal debug log
+pub struct LogLine {
+    pub timestamp: DateTime<Utc>,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl LogLine {
+    pub fn new(level: LogLevel, message: String) -> Self {
+        LogLine {
+            timestamp: Utc::now(),
+            level,
+            message,
+        }
+    }
+
+    pub fn to_ratatui_line(&self, theme: &Theme) -> Line<'_> {
+        let level_style = match self.level {
+            LogLevel::Debug => theme.timestamp,
+            LogLevel::Info => Style::new(),
+            LogLevel::Warn => theme.badge,
+            LogLevel::Error => theme.error,
+        };
+        Line::from(vec![
+            Span::styled(
+                format!(
+                    "{} ",
+                    self.timestamp.with_timezone(&Local).format("%H:%M:%S")
+                ),
+                theme.timestamp,
+            ),
+            Span::styled(format!("{:<5} ", self.level.label()), level_style),
+            Span::raw(self.message.as_str()),
+        ])
+    }
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {:<5} {}",
+            self.timestamp.to_rfc3339(),
+            self.level.label(),
+            self.message
+        )
+    }
+}
+
+/// Output stream of the process a line was read from
+#[derive(Default, Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    #[default]
+    Stdout,
+    Stderr,
+    /// Lines added by the control panel itself, like the separators between runs
+    Marker,
+}
+
 impl StdLine {
-    pub fn new(content: String) -> Self {
+    pub fn new(content: String, stream: Stream) -> Self {
         StdLine {
             timestamp: Utc::now(),
+            stream,
             content,
         }
     }
@@ -26,30 +134,227 @@ impl StdLine {
     }
 
     // Convert StdLine to ratatui::widgets::Line
-    pub fn to_ratatui_line(&self, show_timestamp: bool) -> Line {
-        Line::from(self.to_string(show_timestamp))
+    pub fn to_ratatui_line(&self, show_timestamp: bool, theme: &Theme) -> Line<'_> {
+        let content_style = match self.stream {
+            Stream::Stdout => Style::new(),
+            Stream::Stderr => theme.stderr,
+            Stream::Marker => theme.marker,
+        };
+        let content = Span::styled(self.content.as_str(), content_style);
+        if show_timestamp {
+            return Line::from(vec![
+                Span::styled(
+                    format!("[{}] ", self.timestamp.to_rfc3339()),
+                    theme.timestamp,
+                ),
+                content,
+            ]);
+        }
+        Line::from(content)
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, JsonSchema, Debug)]
 pub struct Cfg {
+    /// Capture mouse events, disable to use the terminal's native selection
+    #[serde(default = "default_mouse")]
+    pub mouse: bool,
+    /// Keys of actions that are rebound, by action name
+    #[serde(default)]
+    pub keys: HashMap<String, KeyBindingCfg>,
+    #[serde(default)]
+    pub theme: ThemeCfg,
+    /// Layout used when the app starts, can be toggled at runtime
+    #[serde(default)]
+    pub layout: LayoutMode,
+    #[serde(default)]
+    pub metrics: MetricsCfg,
+    #[serde(default)]
+    pub debug: DebugCfg,
     pub groups: Vec<GroupCfg>,
+    /// Files the config was merged from in layering order, followed by the local override even
+    /// when it doesn't exist
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
 }
 
-#[derive(Deserialize, Debug)]
+/// Internal log shown in the debug pane
+#[derive(Deserialize, JsonSchema, Debug, Default, Clone)]
+pub struct DebugCfg {
+    /// Messages below this level are not recorded
+    #[serde(default)]
+    pub level: LogLevel,
+    /// File the debug log is appended to as well
+    pub file: Option<String>,
+}
+
+/// Sampling of the CPU, memory, thread and open file usage of the runners' processes
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+pub struct MetricsCfg {
+    /// Show the metrics when the app starts, can be toggled at runtime
+    #[serde(default = "default_metrics_enabled")]
+    pub enabled: bool,
+    /// Add the usage of all descendants to the usage of the runner's process
+    #[serde(default = "default_process_tree")]
+    pub process_tree: bool,
+    #[serde(default = "default_metrics_interval_ms")]
+    pub interval_ms: u64,
+}
+
+impl Default for MetricsCfg {
+    fn default() -> Self {
+        MetricsCfg {
+            enabled: default_metrics_enabled(),
+            process_tree: default_process_tree(),
+            interval_ms: default_metrics_interval_ms(),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Default, Clone)]
+pub struct ThemeCfg {
+    #[serde(default)]
+    pub preset: ThemePreset,
+    /// Colours replacing the ones of the preset, by the name of the `Theme` field. Backgrounds are
+    /// set for `highlight` and `popup`, foregrounds for everything else
+    #[serde(default)]
+    pub colors: HashMap<String, String>,
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+/// One or more key chords bound to an action
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(untagged)]
+pub enum KeyBindingCfg {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBindingCfg {
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            KeyBindingCfg::One(key) => vec![key],
+            KeyBindingCfg::Many(keys) => keys.iter().map(|k| k.as_str()).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    /// Every group is a row and every runner is a pane in it
+    #[default]
+    Grid,
+    /// A list of all runners on the left and the active runner's output next to it
+    Sidebar,
+    /// Every group is a tab and only the active group is shown
+    Tabs,
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
 pub struct GroupCfg {
+    /// Name shown on the group's tab, defaults to `Group <N>`
+    pub name: Option<String>,
+    /// Relative height of the group row compared to the other groups
+    #[serde(default = "default_size")]
+    pub size: u16,
     pub runners: Vec<RunnerCfg>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl GroupCfg {
+    /// Name shown for the group at the given position
+    pub fn display_name(&self, group_index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("Group {}", group_index + 1))
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct RunnerCfg {
     pub name: String,
     #[serde(default = "default_auto_start")]
     pub auto_start: bool,
     #[serde(default = "default_restart_on_finish")]
     pub restart_on_finish: bool,
+    /// Relative width of the runner pane compared to the other panes in its group
+    #[serde(default = "default_size")]
+    pub size: u16,
     pub dir: String,
     pub args: Vec<String>,
+    /// Variables set for the process on top of the inherited environment
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub limits: LimitsCfg,
+    /// Config files that set the runner, starting with the one that defines it
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+}
+
+/// Resource limits of a runner's process, applied with `setrlimit` before it starts
+#[derive(Deserialize, JsonSchema, Debug, Default, Clone, PartialEq)]
+pub struct LimitsCfg {
+    /// Maximum size of the virtual memory, allocations above it fail
+    pub address_space: Option<ByteSize>,
+    /// CPU time after which the process is killed with `SIGXCPU`
+    pub cpu_seconds: Option<u64>,
+    /// Maximum number of open file descriptors
+    pub open_files: Option<u64>,
+    /// Maximum size of core dumps, `0` disables them
+    pub core_size: Option<ByteSize>,
+    /// Restart the runner when the resident memory measured from /proc goes above this size
+    pub restart_above_rss: Option<ByteSize>,
+}
+
+/// Size in bytes, written either as a number or with a unit, e.g. `512M` or `2GiB`
+#[derive(Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "ByteSizeCfg")]
+pub struct ByteSize(pub u64);
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ByteSizeCfg {
+    Bytes(u64),
+    Text(String),
+}
+
+impl TryFrom<ByteSizeCfg> for ByteSize {
+    type Error = String;
+
+    fn try_from(value: ByteSizeCfg) -> Result<Self, Self::Error> {
+        let text = match value {
+            ByteSizeCfg::Bytes(bytes) => return Ok(ByteSize(bytes)),
+            ByteSizeCfg::Text(text) => text,
+        };
+        let trimmed = text.trim();
+        let split = trimmed
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => 1 << 10,
+            "m" | "mb" | "mib" => 1 << 20,
+            "g" | "gb" | "gib" => 1 << 30,
+            "t" | "tb" | "tib" => 1 << 40,
+            _ => return Err(format!("invalid size `{text}`")),
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(multiplier))
+            .map(ByteSize)
+            .ok_or_else(|| format!("invalid size `{text}`"))
+    }
 }
 
 fn default_auto_start() -> bool {
@@ -60,36 +365,161 @@ fn default_restart_on_finish() -> bool {
     false
 }
 
-#[derive(Default)]
+fn default_mouse() -> bool {
+    true
+}
+
+fn default_size() -> u16 {
+    1
+}
+
+fn default_metrics_enabled() -> bool {
+    true
+}
+
+fn default_process_tree() -> bool {
+    true
+}
+
+fn default_metrics_interval_ms() -> u64 {
+    1000
+}
+
 pub enum EventType {
-    #[default]
-    Restart,
+    Restart(RunTrigger),
+    /// The runner's config changed, the process is restarted with the new one if it runs
+    Reload(Box<RunnerCfg>),
     Stop,
-    Finish,
+    /// The output of the process with the given spawn count ended
+    Finish(usize),
     ApplicationQuit,
 }
 
-#[derive(Default)]
 pub struct RunnerEvent {
     pub event_type: EventType,
 }
 
 pub struct Runner {
+    /// Identifies the runner across config reloads, which change its index
+    pub id: usize,
     pub name: String,
-    pub args: Vec<String>,
     pub lines: Vec<StdLine>,
-    // pub horizontal_scroll_state: ScrollbarState,
-    // pub horizontal_scroll_size: usize,
+    pub horizontal_scroll_position: usize,
     pub vertical_scroll_position: usize,
     pub vertical_scroll_size: usize,
+    /// Keep the view at the end of the output as new lines arrive
+    pub follow: bool,
+    /// Soft wrap long lines instead of scrolling horizontally
+    pub wrap: bool,
     pub state: RunnerState,
     pub should_restart: bool,
+    pub size: u16,
+    pub started_at: Option<DateTime<Utc>>,
+    pub pid: Option<u32>,
+    pub restart_count: usize,
+    pub exit_status: Option<ExitStatus>,
+    /// Count of lines that have already been shown on screen
+    pub seen_lines: usize,
+    /// Latest resource usage of the running process
+    pub metrics: Option<ProcessMetrics>,
+    /// Resident memory above which the runner is restarted
+    pub rss_watchdog: Option<u64>,
+    pub config: RunnerCfg,
+    /// Every time the process was started, in order
+    pub runs: Vec<RunSegment>,
+    /// Index of the run whose lines are shown alone, all lines are shown when not set
+    pub shown_run: Option<usize>,
 
     pub tx: Sender<RunnerEvent>,
     pub rx: Receiver<RunnerEvent>,
 }
 
-#[derive(Default)]
+impl Runner {
+    pub fn new(id: usize, cfg: &RunnerCfg) -> Self {
+        let (tx, rx) = unbounded();
+        Runner {
+            id,
+            name: cfg.name.to_owned(),
+            lines: Vec::new(),
+            vertical_scroll_size: 0,
+            vertical_scroll_position: 0,
+            horizontal_scroll_position: 0,
+            follow: true,
+            wrap: false,
+            state: RunnerState::Ready,
+            should_restart: false,
+            size: cfg.size.max(1),
+            started_at: None,
+            pid: None,
+            restart_count: 0,
+            exit_status: None,
+            seen_lines: 0,
+            metrics: None,
+            rss_watchdog: cfg.limits.restart_above_rss.map(|s| s.0),
+            config: cfg.clone(),
+            runs: Vec::new(),
+            shown_run: None,
+            tx,
+            rx,
+        }
+    }
+
+    /// Range of the lines that are shown, either all of them or the ones of a single run
+    pub fn shown_lines(&self) -> Range<usize> {
+        let Some(run) = self.shown_run else {
+            return 0..self.lines.len();
+        };
+        let end = self
+            .runs
+            .get(run + 1)
+            .map_or(self.lines.len(), |r| r.first_line)
+            .min(self.lines.len());
+        let start = self.runs.get(run).map_or(0, |r| r.first_line).min(end);
+        start..end
+    }
+}
+
+/// A single run of a runner's process
+pub struct RunSegment {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub exit_status: Option<ExitStatus>,
+    pub trigger: RunTrigger,
+    /// Index of the first line of the run, which is the separator for all but the first run
+    pub first_line: usize,
+}
+
+/// Reason a process was started
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunTrigger {
+    /// Started together with the app
+    AutoStart,
+    /// Started or restarted with a key
+    Manual,
+    /// Restarted by `restart_on_finish` after exiting with an error
+    Crash,
+    /// Restarted by `restart_on_finish` after exiting successfully
+    Finish,
+    /// Restarted because its memory usage went above the limit
+    Watchdog,
+    /// Restarted because its config changed
+    Reload,
+}
+
+impl RunTrigger {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RunTrigger::AutoStart => "auto start",
+            RunTrigger::Manual => "manual",
+            RunTrigger::Crash => "crash",
+            RunTrigger::Finish => "finish",
+            RunTrigger::Watchdog => "memory watchdog",
+            RunTrigger::Reload => "config reload",
+        }
+    }
+}
+
+#[derive(Default, PartialEq)]
 pub enum RunnerState {
     #[default]
     Ready,
@@ -98,21 +528,149 @@ pub enum RunnerState {
     Finish,
 }
 
+impl RunnerState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RunnerState::Ready => "ready",
+            RunnerState::Active => "active",
+            RunnerState::Error => "error",
+            RunnerState::Finish => "finished",
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct App {
+    pub keymap: Keymap,
+    pub theme: Theme,
     pub runners: Vec<Runner>,
     pub groups: Vec<Group>,
     pub active_runner: usize,
+    pub layout: LayoutMode,
     pub has_zoomed_runner: bool,
     pub zoomed_runner: usize,
     pub should_exit: bool,
     pub show_timestamps: bool,
     pub show_help: bool,
+    /// Show the details popup of the active runner
+    pub show_details: bool,
+    pub details_scroll: usize,
+    /// Path of the config file the runners were loaded from
+    pub config_path: PathBuf,
+    /// Files the config is merged from, the config watcher reloads when one of them changes
+    pub config_files: Vec<PathBuf>,
+    /// Format the config was loaded with when it was given instead of taken from the extension
+    pub config_format: Option<Format>,
+    /// Selection of runners from the command line, applied again on every reload
+    pub config_overrides: Overrides,
+    /// Set by the reload key, the config watcher reloads the config when it sees it
+    pub reload_requested: bool,
+    /// Id given to the next runner that is created
+    pub next_runner_id: usize,
     pub show_debug: bool,
-    pub debug_lines: Vec<StdLine>,
+    /// Show the resource usage at the bottom of the runner panes
+    pub show_metrics: bool,
+    pub debug_lines: Vec<LogLine>,
+    /// Lowest level that is recorded in the debug log
+    pub debug_record_level: LogLevel,
+    /// Lowest level that is shown in the debug pane
+    pub debug_level: LogLevel,
+    /// Text that the lines shown in the debug pane have to contain
+    pub debug_filter: String,
+    /// Count of lines the debug pane is scrolled up from the latest line
+    pub debug_scroll: usize,
+    pub debug_file: Option<File>,
+    /// Areas of the runner panes from the last draw, used for mouse hit-testing
+    pub pane_areas: Vec<(usize, Rect)>,
+    /// Areas of the group rows from the last draw, used for mouse hit-testing
+    pub group_areas: Vec<(usize, Rect)>,
+    /// Area of the sidebar list from the last draw, used for mouse hit-testing
+    pub sidebar_area: Option<Rect>,
+    /// Index of the first runner shown in the sidebar, kept so the active runner stays visible
+    pub sidebar_offset: usize,
+    pub resize_drag: Option<ResizeDrag>,
+    /// Message shown in the status bar instead of the key hints until it expires
+    pub status_message: Option<(String, Instant)>,
+    /// Time and runner of the last click, used to detect double clicks
+    pub last_click: Option<(Instant, usize)>,
+    pub mode: Mode,
+    /// Text typed into the prompt of the current mode
+    pub input: String,
+    /// Error of the last prompt action, shown next to the prompt
+    pub input_error: Option<String>,
+    pub selection: Option<Selection>,
+    /// Whether the selection is still being dragged with the mouse
+    pub is_selecting: bool,
+}
+
+impl App {
+    pub fn runner_mut(&mut self, id: usize) -> Option<&mut Runner> {
+        self.runners.iter_mut().find(|r| r.id == id)
+    }
+
+    pub fn notify(&mut self, message: String) {
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    /// Returns the group index and the position inside that group of a runner
+    pub fn group_of(&self, runner_index: usize) -> Option<(usize, usize)> {
+        self.groups
+            .iter()
+            .enumerate()
+            .find_map(|(group_index, group)| {
+                group
+                    .runners
+                    .iter()
+                    .position(|r| *r == runner_index)
+                    .map(|position| (group_index, position))
+            })
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct Group {
+    pub name: String,
     pub runners: Vec<usize>,
+    pub size: u16,
+    /// Size set in the config, a reload only replaces the current size when this changes
+    pub config_size: u16,
+}
+
+/// Decides how key presses are handled
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    /// Line selection in the active pane, started with `v`
+    Visual,
+    /// Typing the path to export the active runner's lines to
+    Export,
+    /// Typing the text the debug pane is filtered by
+    DebugFilter,
+}
+
+/// Range of lines selected in a runner pane
+#[derive(Debug, Clone, Copy)]
+pub struct Selection {
+    pub runner: usize,
+    /// Line where the selection started
+    pub anchor: usize,
+    /// Line where the selection currently ends, can be before the anchor
+    pub cursor: usize,
+}
+
+impl Selection {
+    /// Selected line indexes in ascending order
+    pub fn lines(&self) -> Range<usize> {
+        self.anchor.min(self.cursor)..self.anchor.max(self.cursor) + 1
+    }
+}
+
+/// Border that is currently being dragged with the mouse
+#[derive(Debug, Clone, Copy)]
+pub enum ResizeDrag {
+    /// Border between the pane at `position` and the next one in `group`
+    Horizontal { group: usize, position: usize },
+    /// Border between the group row `group` and the one below it
+    Vertical { group: usize },
 }
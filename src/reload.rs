@@ -0,0 +1,216 @@
+use crate::config;
+use crate::keys::Keymap;
+use crate::models::{App, EventType, Group, LogLevel, Mode, Runner, RunnerCfg, RunnerEvent};
+use crate::theme::Theme;
+use crate::utils::log_debug;
+use crate::worker::start_worker;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The files the config is merged from with their modification times
+fn modified_at(app: &Arc<Mutex<App>>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let files = app.lock().unwrap().config_files.clone();
+    files
+        .into_iter()
+        .map(|file| {
+            let modified = fs::metadata(&file).and_then(|m| m.modified()).ok();
+            (file, modified)
+        })
+        .collect()
+}
+
+/// Reloads the config when the file changes or a reload is requested, until the app exits
+pub fn start_watcher(app: Arc<Mutex<App>>) {
+    let path = app.lock().unwrap().config_path.clone();
+    let mut last_modified = modified_at(&app);
+    let mut handlers: Vec<JoinHandle<()>> = vec![];
+
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+
+        let requested = {
+            let mut app = app.lock().unwrap();
+            if app.should_exit {
+                break;
+            }
+            std::mem::take(&mut app.reload_requested)
+        };
+
+        let modified = modified_at(&app);
+        if modified == last_modified && !requested {
+            continue;
+        }
+        last_modified = modified;
+
+        handlers.extend(reload(&app, &path));
+        // A reload can change which files are included, their times are taken from now on
+        let files = app.lock().unwrap().config_files.clone();
+        if !last_modified.iter().map(|(file, _)| file).eq(files.iter()) {
+            last_modified = modified_at(&app);
+        }
+    }
+
+    for handle in handlers {
+        handle.join().expect("Handler paniced");
+    }
+}
+
+/// Applies the config file to the running app, only runners whose config changed are restarted
+fn reload(app: &Arc<Mutex<App>>, path: &Path) -> Vec<JoinHandle<()>> {
+    let (format, overrides) = {
+        let app = app.lock().unwrap();
+        (app.config_format, app.config_overrides.clone())
+    };
+    let cfg = config::load_filtered(&path.to_string_lossy(), format, &overrides);
+    let mut app_guard = app.lock().unwrap();
+    let app_ref = &mut *app_guard;
+
+    let cfg = match cfg {
+        Ok(cfg) => cfg,
+        Err(errors) => {
+            app_ref.notify(format!(
+                "Config not reloaded, {} error(s): {}",
+                errors.len(),
+                errors[0]
+            ));
+            for err in errors {
+                log_debug(
+                    app_ref,
+                    LogLevel::Error,
+                    format!("Config not reloaded: {err}"),
+                );
+            }
+            return vec![];
+        }
+    };
+
+    // Both were validated by the config loader
+    app_ref.keymap = Keymap::from_config(&cfg.keys).unwrap_or_default();
+    app_ref.theme = Theme::from_config(&cfg.theme).unwrap_or_default();
+    app_ref.debug_record_level = cfg.debug.level;
+    app_ref.config_files = cfg.files.clone();
+
+    let active_id = app_ref.runners.get(app_ref.active_runner).map(|r| r.id);
+    let zoomed_id = app_ref
+        .runners
+        .get(app_ref.zoomed_runner)
+        .filter(|_| app_ref.has_zoomed_runner)
+        .map(|r| r.id);
+
+    let mut previous: HashMap<String, Runner> = std::mem::take(&mut app_ref.runners)
+        .into_iter()
+        .map(|r| (r.name.clone(), r))
+        .collect();
+
+    let mut added: Vec<(RunnerCfg, usize)> = vec![];
+    let mut restarted = 0;
+    let mut unchanged = 0;
+    let mut groups = vec![];
+    for (group_index, r_group) in cfg.groups.iter().enumerate() {
+        let mut group: Vec<usize> = vec![];
+        for r_cfg in r_group.runners.iter() {
+            group.push(app_ref.runners.len());
+            let Some(mut runner) = previous.remove(&r_cfg.name) else {
+                let id = app_ref.next_runner_id;
+                app_ref.next_runner_id += 1;
+                app_ref.runners.push(Runner::new(id, r_cfg));
+                added.push((r_cfg.clone(), id));
+                continue;
+            };
+
+            // A changed pane size is applied without restarting the process, otherwise the size
+            // the user resized the pane to is kept
+            if r_cfg.size != runner.config.size {
+                runner.size = r_cfg.size.max(1);
+            }
+            let changed = RunnerCfg {
+                size: runner.config.size,
+                files: runner.config.files.clone(),
+                ..r_cfg.clone()
+            } != runner.config;
+            runner.config = r_cfg.clone();
+            if changed {
+                runner.rss_watchdog = r_cfg.limits.restart_above_rss.map(|s| s.0);
+                let _ = runner.tx.send(RunnerEvent {
+                    event_type: EventType::Reload(Box::new(r_cfg.clone())),
+                });
+                restarted += 1;
+            } else {
+                unchanged += 1;
+            }
+            app_ref.runners.push(runner);
+        }
+        let name = r_group.display_name(group_index);
+        let size = match app_ref.groups.iter().find(|g| g.name == name) {
+            Some(previous) if previous.config_size == r_group.size => previous.size,
+            _ => r_group.size.max(1),
+        };
+        groups.push(Group {
+            name,
+            runners: group,
+            size,
+            config_size: r_group.size,
+        });
+    }
+    app_ref.groups = groups;
+
+    let removed = previous.len();
+    for runner in previous.into_values() {
+        let _ = runner.tx.send(RunnerEvent {
+            event_type: EventType::ApplicationQuit,
+        });
+        log_debug(
+            app_ref,
+            LogLevel::Info,
+            format!("{} removed from the config", runner.name),
+        );
+    }
+
+    // Indices from before the reload no longer point to the same runners
+    let position_of =
+        |id: Option<usize>| id.and_then(|id| app_ref.runners.iter().position(|r| r.id == id));
+    app_ref.active_runner = position_of(active_id).unwrap_or(0);
+    match position_of(zoomed_id) {
+        Some(index) => app_ref.zoomed_runner = index,
+        None => {
+            app_ref.has_zoomed_runner = false;
+            app_ref.zoomed_runner = 0;
+        }
+    }
+    app_ref.selection = None;
+    app_ref.is_selecting = false;
+    if app_ref.mode == Mode::Visual {
+        app_ref.mode = Mode::Normal;
+    }
+    app_ref.pane_areas.clear();
+    app_ref.group_areas.clear();
+    app_ref.sidebar_area = None;
+    app_ref.resize_drag = None;
+    app_ref.last_click = None;
+
+    let message = format!(
+        "Config reloaded: {} added, {removed} removed, {restarted} restarted, {unchanged} unchanged",
+        added.len()
+    );
+    log_debug(app_ref, LogLevel::Info, message.clone());
+    app_ref.notify(message);
+    drop(app_guard);
+
+    added
+        .into_iter()
+        .map(|(runner_config, runner_id)| {
+            let runner_app = app.clone();
+            thread::spawn(move || start_worker(runner_app, runner_config, runner_id))
+        })
+        .collect()
+}
@@ -0,0 +1,163 @@
+use crate::models::{RunnerState, ThemeCfg, ThemePreset};
+
+use ratatui::style::{Color, Modifier, Style};
+use std::{env, str::FromStr};
+
+/// Styles of everything in the UI that carries a meaning through its colour
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub ready: Style,
+    pub active: Style,
+    pub error: Style,
+    pub finish: Style,
+    /// Patched over the state style of the active pane's border
+    pub active_border: Style,
+    pub stderr: Style,
+    /// Separators between runs
+    pub marker: Style,
+    /// Selected lines
+    pub highlight: Style,
+    pub timestamp: Style,
+    /// Background of the help and debug popups
+    pub popup: Style,
+    pub popup_border: Style,
+    /// Count of unread lines in the sidebar
+    pub badge: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::preset(ThemePreset::Dark)
+    }
+}
+
+impl Theme {
+    pub fn preset(preset: ThemePreset) -> Self {
+        match preset {
+            ThemePreset::Dark => Theme {
+                ready: Style::new().fg(Color::Blue),
+                active: Style::new().fg(Color::Gray),
+                error: Style::new().fg(Color::Red),
+                finish: Style::new().fg(Color::Green),
+                active_border: Style::new().add_modifier(Modifier::BOLD),
+                stderr: Style::new().fg(Color::LightRed),
+                marker: Style::new().fg(Color::Cyan),
+                highlight: Style::new().add_modifier(Modifier::REVERSED),
+                timestamp: Style::new().fg(Color::DarkGray),
+                popup: Style::new().bg(Color::Black),
+                popup_border: Style::new().fg(Color::Green),
+                badge: Style::new().fg(Color::Yellow),
+            },
+            ThemePreset::Light => Theme {
+                ready: Style::new().fg(Color::Blue),
+                active: Style::new().fg(Color::Black),
+                error: Style::new().fg(Color::Red),
+                finish: Style::new().fg(Color::Green),
+                active_border: Style::new().add_modifier(Modifier::BOLD),
+                stderr: Style::new().fg(Color::Red),
+                marker: Style::new().fg(Color::Blue),
+                highlight: Style::new().bg(Color::LightBlue).fg(Color::Black),
+                timestamp: Style::new().fg(Color::Gray),
+                popup: Style::new().bg(Color::White).fg(Color::Black),
+                popup_border: Style::new().fg(Color::Blue),
+                badge: Style::new().fg(Color::Magenta),
+            },
+            ThemePreset::HighContrast => Theme {
+                ready: Style::new()
+                    .fg(Color::LightCyan)
+                    .add_modifier(Modifier::BOLD),
+                active: Style::new().fg(Color::White),
+                error: Style::new()
+                    .fg(Color::LightRed)
+                    .add_modifier(Modifier::BOLD),
+                finish: Style::new().fg(Color::LightGreen),
+                active_border: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                stderr: Style::new().fg(Color::LightRed),
+                marker: Style::new()
+                    .fg(Color::LightCyan)
+                    .add_modifier(Modifier::BOLD),
+                highlight: Style::new().bg(Color::Yellow).fg(Color::Black),
+                timestamp: Style::new().fg(Color::LightCyan),
+                popup: Style::new().bg(Color::Black).fg(Color::White),
+                popup_border: Style::new().fg(Color::Yellow),
+                badge: Style::new()
+                    .fg(Color::LightYellow)
+                    .add_modifier(Modifier::BOLD),
+            },
+        }
+    }
+
+    /// Builds the theme from the preset with the custom colours applied on top, colours are
+    /// dropped when the `NO_COLOR` environment variable is set
+    pub fn from_config(cfg: &ThemeCfg) -> Result<Self, Vec<(String, String)>> {
+        let mut theme = Theme::preset(cfg.preset);
+        let mut errors = vec![];
+
+        for (name, value) in cfg.colors.iter() {
+            let color = match Color::from_str(value) {
+                Ok(color) => color,
+                Err(_) => {
+                    errors.push((name.clone(), format!("{name}: unknown colour `{value}`")));
+                    continue;
+                }
+            };
+            match name.as_str() {
+                "ready" => theme.ready = theme.ready.fg(color),
+                "active" => theme.active = theme.active.fg(color),
+                "error" => theme.error = theme.error.fg(color),
+                "finish" => theme.finish = theme.finish.fg(color),
+                "active_border" => theme.active_border = theme.active_border.fg(color),
+                "stderr" => theme.stderr = theme.stderr.fg(color),
+                "marker" => theme.marker = theme.marker.fg(color),
+                "highlight" => {
+                    theme.highlight = theme
+                        .highlight
+                        .bg(color)
+                        .remove_modifier(Modifier::REVERSED)
+                }
+                "timestamp" => theme.timestamp = theme.timestamp.fg(color),
+                "popup" => theme.popup = theme.popup.bg(color),
+                "popup_border" => theme.popup_border = theme.popup_border.fg(color),
+                "badge" => theme.badge = theme.badge.fg(color),
+                _ => errors.push((name.clone(), format!("unknown theme colour `{name}`"))),
+            }
+        }
+
+        if !errors.is_empty() {
+            errors.sort();
+            return Err(errors);
+        }
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            theme = theme.without_colors();
+        }
+        Ok(theme)
+    }
+
+    /// Keeps only the modifiers, selected lines fall back to reversed colours to stay visible
+    fn without_colors(self) -> Self {
+        let strip = |style: Style| Style::new().add_modifier(style.add_modifier);
+        Theme {
+            ready: strip(self.ready),
+            active: strip(self.active),
+            error: strip(self.error),
+            finish: strip(self.finish),
+            active_border: strip(self.active_border),
+            stderr: strip(self.stderr),
+            marker: strip(self.marker),
+            highlight: Style::new().add_modifier(Modifier::REVERSED),
+            timestamp: strip(self.timestamp),
+            popup: strip(self.popup),
+            popup_border: strip(self.popup_border),
+            badge: strip(self.badge),
+        }
+    }
+
+    pub fn state(&self, state: &RunnerState) -> Style {
+        match state {
+            RunnerState::Ready => self.ready,
+            RunnerState::Error => self.error,
+            RunnerState::Active => self.active,
+            RunnerState::Finish => self.finish,
+        }
+    }
+}
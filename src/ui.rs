@@ -1,25 +1,40 @@
+use crate::clipboard;
+use crate::export;
+use crate::keys::Action;
+use crate::metrics::{format_bytes, sparkline, ProcessMetrics};
 use crate::models::*;
+use crate::utils::log_debug;
+use crate::worker::format_exit;
 
+use chrono::{Local, Utc};
 use std::{
-    io, iter,
+    collections::HashMap,
+    env, fs, io,
+    ops::Range,
+    path::Path,
     sync::{Arc, Mutex, MutexGuard},
     time::{Duration, Instant},
     vec,
 };
 
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode},
-    layout::{Constraint, Flex, Layout, Margin, Rect},
+    crossterm::event::{
+        self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
+    layout::{Constraint, Flex, Layout, Margin, Position, Rect},
     prelude::Backend,
-    style::{Color, Style},
-    text::Line,
-    widgets::{Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Block, BorderType, Clear, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Tabs, Wrap,
+    },
     Frame, Terminal,
 };
 
 pub fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
-    mut app: &mut Arc<Mutex<App>>,
+    app: &mut Arc<Mutex<App>>,
     tick_rate: Duration,
 ) -> Result<(), B::Error>
 where
@@ -27,97 +42,33 @@ where
 {
     let mut last_tick = Instant::now();
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        // Also set when a signal asks the app to quit
+        if app.lock().unwrap().should_exit {
+            return Ok(());
+        }
+        terminal.draw(|f| ui(f, app))?;
 
         let timeout = tick_rate.saturating_sub(last_tick.elapsed());
         if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
+            let event = event::read()?;
+            if let Event::Mouse(mouse) = event {
+                handle_mouse(&mut app.lock().unwrap(), mouse);
+            }
+            if let Event::Key(key) = event {
                 let mut app = app.lock().unwrap();
-                match key.code {
-                    KeyCode::Char('q') => {
-                        app.should_exit = true;
-                        for runner in app.runners.iter() {
-                            let _ = runner.tx.send(RunnerEvent {
-                                event_type: EventType::ApplicationQuit,
-                            });
-                        }
-                        return Ok(());
-                    }
-                    KeyCode::Char('r') => {
-                        let active_runner = app.active_runner;
-                        let active_runner = app.runners.get_mut(active_runner).unwrap();
-                        active_runner.should_restart = true;
-
-                        let _ = active_runner.tx.send(RunnerEvent {
-                            event_type: EventType::Restart,
-                        });
-                    }
-                    KeyCode::Char('s') => {
-                        let active_runner = app.active_runner;
-                        let active_runner = app.runners.get_mut(active_runner).unwrap();
-                        active_runner.should_restart = true;
-
-                        let _ = active_runner.tx.send(RunnerEvent {
-                            event_type: EventType::Stop,
-                        });
-                    }
-                    KeyCode::Char('n') => {
-                        if app.active_runner == app.runners.len() - 1 {
-                            app.active_runner = 0;
-                        } else {
-                            app.active_runner += 1;
-                        }
-                    }
-                    KeyCode::Char('p') => {
-                        if app.active_runner == 0 {
-                            app.active_runner = app.runners.len() - 1;
-                        } else {
-                            app.active_runner -= 1;
-                        }
-                    }
-                    KeyCode::Char('t') => {
-                        app.show_timestamps = !app.show_timestamps;
+                if app.mode == Mode::Export {
+                    handle_export_key(&mut app, key);
+                } else if app.mode == Mode::DebugFilter {
+                    handle_debug_filter_key(&mut app, key.code);
+                } else if let Some(action) = app.keymap.action_for(&key) {
+                    if app.mode == Mode::Visual {
+                        handle_visual_action(&mut app, action);
+                    } else {
+                        handle_action(&mut app, action);
                     }
-                    KeyCode::Char('z') => {
-                        if app.has_zoomed_runner {
-                            app.has_zoomed_runner = false;
-                        } else {
-                            app.has_zoomed_runner = true;
-                            app.zoomed_runner = app.active_runner;
-                        }
-                    }
-                    KeyCode::Char('d') => {
-                        app.show_debug = !app.show_debug;
-                    }
-                    KeyCode::Char('?') => {
-                        app.show_help = true;
-                    }
-                    KeyCode::Esc => {
-                        app.show_help = false;
-                    }
-                    KeyCode::Char('j') | KeyCode::Down => {
-                        let active_runner = app.active_runner;
-                        let active_runner = app.runners.get_mut(active_runner).unwrap();
-                        active_runner.vertical_scroll_position = if active_runner
-                            .vertical_scroll_position
-                            < active_runner.vertical_scroll_size
-                        {
-                            active_runner.vertical_scroll_position.saturating_add(1)
-                        } else {
-                            active_runner.vertical_scroll_position
-                        };
-                    }
-                    KeyCode::Char('k') | KeyCode::Up => {
-                        let active_runner = app.active_runner;
-                        let active_runner = app.runners.get_mut(active_runner).unwrap();
-                        active_runner.vertical_scroll_position =
-                            if active_runner.vertical_scroll_position > 0 {
-                                active_runner.vertical_scroll_position.saturating_sub(1)
-                            } else {
-                                active_runner.vertical_scroll_position
-                            };
-                    }
-                    _ => (),
+                }
+                if app.should_exit {
+                    return Ok(());
                 }
             }
         }
@@ -128,8 +79,848 @@ where
     }
 }
 
+/// Stops all runners and makes the UI exit
+pub fn quit(app: &mut App, reason: &str) {
+    app.should_exit = true;
+    log_debug(app, LogLevel::Info, reason.into());
+    for runner_index in 0..app.runners.len() {
+        send_event(app, runner_index, EventType::ApplicationQuit);
+    }
+}
+
+fn handle_action(app: &mut App, action: Action) {
+    // The scroll keys move the details popup while it is open
+    if app.show_details {
+        match action {
+            Action::ScrollDown => {
+                app.details_scroll = app.details_scroll.saturating_add(1);
+                return;
+            }
+            Action::ScrollUp => {
+                app.details_scroll = app.details_scroll.saturating_sub(1);
+                return;
+            }
+            Action::Top => {
+                app.details_scroll = 0;
+                return;
+            }
+            _ => (),
+        }
+    }
+
+    match action {
+        Action::Quit => quit(app, "Quitting, stopping all runners"),
+        Action::Restart => {
+            let active_runner = app.active_runner;
+            app.runners[active_runner].should_restart = true;
+            send_event(app, active_runner, EventType::Restart(RunTrigger::Manual));
+
+            let message = format!("Restarted {}", app.runners[active_runner].name);
+            log_debug(app, LogLevel::Info, message.clone());
+            app.notify(message);
+        }
+        Action::Stop => {
+            let active_runner = app.active_runner;
+            app.runners[active_runner].should_restart = true;
+            send_event(app, active_runner, EventType::Stop);
+
+            let message = format!("Stopped {}", app.runners[active_runner].name);
+            log_debug(app, LogLevel::Info, message.clone());
+            app.notify(message);
+        }
+        Action::NextPane => {
+            if app.active_runner == app.runners.len() - 1 {
+                app.active_runner = 0;
+            } else {
+                app.active_runner += 1;
+            }
+        }
+        Action::PreviousPane => {
+            if app.active_runner == 0 {
+                app.active_runner = app.runners.len() - 1;
+            } else {
+                app.active_runner -= 1;
+            }
+        }
+        Action::ToggleTimestamps => {
+            app.show_timestamps = !app.show_timestamps;
+        }
+        Action::Zoom => {
+            if app.has_zoomed_runner {
+                app.has_zoomed_runner = false;
+            } else {
+                app.has_zoomed_runner = true;
+                app.zoomed_runner = app.active_runner;
+            }
+        }
+        Action::ToggleDebug => {
+            app.show_debug = !app.show_debug;
+        }
+        Action::CycleLayout => {
+            app.layout = match app.layout {
+                LayoutMode::Grid => LayoutMode::Sidebar,
+                LayoutMode::Sidebar => LayoutMode::Tabs,
+                LayoutMode::Tabs => LayoutMode::Grid,
+            };
+        }
+        Action::NextGroup => {
+            cycle_group(app, true);
+        }
+        Action::PreviousGroup => {
+            cycle_group(app, false);
+        }
+        Action::Group(group_index) => {
+            if group_index < app.groups.len() {
+                select_group(app, group_index);
+            }
+        }
+        Action::GrowPane => {
+            resize_active_pane(app, 1);
+        }
+        Action::ShrinkPane => {
+            resize_active_pane(app, -1);
+        }
+        Action::GrowRow => {
+            resize_active_group(app, 1);
+        }
+        Action::ShrinkRow => {
+            resize_active_group(app, -1);
+        }
+        Action::Select => {
+            start_visual_mode(app);
+        }
+        Action::Export => {
+            start_export(app);
+        }
+        Action::Help => {
+            app.show_help = true;
+        }
+        Action::ReloadConfig => app.reload_requested = true,
+        Action::Details => {
+            app.show_details = !app.show_details;
+            app.details_scroll = 0;
+        }
+        Action::Cancel => {
+            app.show_help = false;
+            app.show_details = false;
+            app.selection = None;
+        }
+        Action::ScrollDown => {
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, 1);
+        }
+        Action::ScrollUp => {
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, -1);
+        }
+        Action::PageDown => {
+            let page = page_size(app, app.active_runner);
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, page);
+        }
+        Action::PageUp => {
+            let page = page_size(app, app.active_runner);
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, -page);
+        }
+        Action::HalfPageDown => {
+            let page = page_size(app, app.active_runner);
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, page / 2);
+        }
+        Action::HalfPageUp => {
+            let page = page_size(app, app.active_runner);
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, -page / 2);
+        }
+        Action::Top => {
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, isize::MIN);
+        }
+        Action::Bottom => {
+            let active_runner = app.active_runner;
+            scroll_runner(app, active_runner, isize::MAX);
+        }
+        Action::ToggleFollow => {
+            let active_runner = app.active_runner;
+            let active_runner = app.runners.get_mut(active_runner).unwrap();
+            active_runner.follow = !active_runner.follow;
+            if active_runner.follow {
+                active_runner.vertical_scroll_position = active_runner.vertical_scroll_size;
+            }
+        }
+        Action::ScrollRight => {
+            scroll_active_runner_horizontally(app, HORIZONTAL_SCROLL_COLUMNS);
+        }
+        Action::ScrollLeft => {
+            scroll_active_runner_horizontally(app, -HORIZONTAL_SCROLL_COLUMNS);
+        }
+        Action::ToggleWrap => {
+            let active_runner = app.active_runner;
+            let active_runner = app.runners.get_mut(active_runner).unwrap();
+            active_runner.wrap = !active_runner.wrap;
+            active_runner.horizontal_scroll_position = 0;
+        }
+        Action::ToggleMetrics => {
+            app.show_metrics = !app.show_metrics;
+        }
+        Action::DebugScrollUp => {
+            app.show_debug = true;
+            app.debug_scroll = app.debug_scroll.saturating_add(1);
+        }
+        Action::DebugScrollDown => {
+            app.show_debug = true;
+            app.debug_scroll = app.debug_scroll.saturating_sub(1);
+        }
+        Action::DebugLevel => {
+            app.show_debug = true;
+            app.debug_level = app.debug_level.next();
+            app.debug_scroll = 0;
+        }
+        Action::DebugFilter => {
+            app.show_debug = true;
+            app.input = app.debug_filter.clone();
+            app.input_error = None;
+            app.mode = Mode::DebugFilter;
+        }
+        Action::ClearPane => {
+            let active_runner = app.active_runner;
+            clear_runner(app, active_runner);
+        }
+        Action::PreviousRun => {
+            show_run(app, false);
+        }
+        Action::NextRun => {
+            show_run(app, true);
+        }
+        Action::Yank | Action::YankWithTimestamps => (),
+    }
+}
+
+/// Drops all lines of a runner, earlier runs are left without lines
+fn clear_runner(app: &mut App, runner_index: usize) {
+    let runner = &mut app.runners[runner_index];
+    runner.lines.clear();
+    runner.vertical_scroll_size = 0;
+    runner.vertical_scroll_position = 0;
+    runner.horizontal_scroll_position = 0;
+    runner.seen_lines = 0;
+    runner.follow = true;
+    for run in runner.runs.iter_mut() {
+        run.first_line = 0;
+    }
+    let message = format!("Cleared {}", runner.name);
+    if app.selection.is_some_and(|s| s.runner == runner_index) {
+        app.selection = None;
+    }
+    app.notify(message);
+}
+
+/// Steps the active runner's view through its runs, stepping back from all runs shows the current
+/// run and stepping forward from the current run shows all runs again
+fn show_run(app: &mut App, forward: bool) {
+    let runner = &mut app.runners[app.active_runner];
+    let count = runner.runs.len();
+    if count == 0 {
+        return;
+    }
+    runner.shown_run = match (runner.shown_run, forward) {
+        (None, false) => Some(count - 1),
+        (None, true) => None,
+        (Some(run), false) => Some(run.saturating_sub(1)),
+        (Some(run), true) if run + 1 < count => Some(run + 1),
+        (Some(_), true) => None,
+    };
+    let shown = runner.shown_lines();
+    runner.vertical_scroll_position = shown.end;
+    runner.follow = shown.end == runner.lines.len();
+    let message = match runner.shown_run {
+        Some(run) => format!("Showing run {} of {} of {}", run + 1, count, runner.name),
+        None => format!("Showing all runs of {}", runner.name),
+    };
+    if app.selection.is_some_and(|s| s.runner == app.active_runner) {
+        app.selection = None;
+    }
+    app.notify(message);
+}
+
+/// Starts a line selection on the last visible line of the active pane
+fn start_visual_mode(app: &mut App) {
+    let runner_index = app.active_runner;
+    let Some(area) = pane_area(app, runner_index) else {
+        return;
+    };
+    let visible = pane_view(
+        &app.runners[runner_index],
+        area.inner(Margin::new(1, 1)),
+        app.show_timestamps,
+    )
+    .lines;
+    if visible.is_empty() {
+        return;
+    }
+    app.selection = Some(Selection {
+        runner: runner_index,
+        anchor: visible.end - 1,
+        cursor: visible.end - 1,
+    });
+    app.mode = Mode::Visual;
+}
+
+fn handle_visual_action(app: &mut App, action: Action) {
+    let Some(selection) = app.selection else {
+        app.mode = Mode::Normal;
+        return;
+    };
+    match action {
+        Action::ScrollDown => move_selection_cursor(app, selection, 1),
+        Action::ScrollUp => move_selection_cursor(app, selection, -1),
+        Action::Yank => {
+            copy_selection(app, &selection, app.show_timestamps);
+            app.mode = Mode::Normal;
+            app.selection = None;
+        }
+        Action::YankWithTimestamps => {
+            copy_selection(app, &selection, true);
+            app.mode = Mode::Normal;
+            app.selection = None;
+        }
+        Action::Export => start_export(app),
+        Action::Quit => handle_action(app, action),
+        Action::Select | Action::Cancel => {
+            app.mode = Mode::Normal;
+            app.selection = None;
+        }
+        _ => (),
+    }
+}
+
+/// Opens the prompt for the path to export the active runner's lines to
+fn start_export(app: &mut App) {
+    app.input = export::default_file_name(&app.runners[app.active_runner].name);
+    app.input_error = None;
+    app.mode = Mode::Export;
+}
+
+fn handle_export_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        // Chords like ctrl+c are not part of the file name
+        KeyCode::Char(_)
+            if key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {}
+        KeyCode::Char(c) => app.input.push(c),
+        KeyCode::Backspace => {
+            app.input.pop();
+        }
+        KeyCode::Enter => {
+            // Only the selected lines are exported when there is a selection in the active pane
+            let runner = &app.runners[app.active_runner];
+            let lines = match app.selection.filter(|s| s.runner == app.active_runner) {
+                Some(selection) => {
+                    let selected = selection.lines();
+                    &runner.lines[selected.start.min(runner.lines.len())
+                        ..selected.end.min(runner.lines.len())]
+                }
+                None => &runner.lines[runner.shown_lines()],
+            };
+            let path = app.input.trim().to_string();
+            let line_count = lines.len();
+            match export::export_lines(Path::new(&path), &runner.name, lines, app.show_timestamps) {
+                Ok(()) => {
+                    app.mode = Mode::Normal;
+                    app.selection = None;
+                    app.input.clear();
+                    let message = format!("Exported {line_count} lines to {path}");
+                    log_debug(app, LogLevel::Info, message.clone());
+                    app.notify(message);
+                }
+                Err(err) => {
+                    log_debug(
+                        app,
+                        LogLevel::Error,
+                        format!("Exporting to {path} failed: {err}"),
+                    );
+                    app.input_error = Some(err.to_string());
+                }
+            }
+        }
+        KeyCode::Esc => {
+            app.mode = Mode::Normal;
+            app.input.clear();
+        }
+        _ => (),
+    }
+}
+
+/// Filters the debug pane while the text is typed, escape removes the filter
+fn handle_debug_filter_key(app: &mut App, key: KeyCode) {
+    match key {
+        KeyCode::Char(c) => app.input.push(c),
+        KeyCode::Backspace => {
+            app.input.pop();
+        }
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            app.input.clear();
+            return;
+        }
+        KeyCode::Esc => {
+            app.mode = Mode::Normal;
+            app.input.clear();
+        }
+        _ => (),
+    }
+    app.debug_filter = app.input.clone();
+    app.debug_scroll = 0;
+}
+
+/// Sends an event to the worker of a runner
+fn send_event(app: &mut App, runner_index: usize, event_type: EventType) {
+    let runner = &app.runners[runner_index];
+    if runner.tx.send(RunnerEvent { event_type }).is_err() {
+        let message = format!("{}: the worker is no longer running", runner.name);
+        log_debug(app, LogLevel::Error, message);
+    }
+}
+
+/// Moves the moving end of the selection and scrolls the pane so it stays visible
+fn move_selection_cursor(app: &mut App, selection: Selection, delta: isize) {
+    let height = pane_area(app, selection.runner)
+        .map_or(1, |area| usize::from(area.height.saturating_sub(2)).max(1));
+    let runner = &mut app.runners[selection.runner];
+    let shown = runner.shown_lines();
+    let cursor = selection
+        .cursor
+        .saturating_add_signed(delta)
+        .clamp(shown.start, shown.end.saturating_sub(1).max(shown.start));
+
+    if cursor >= runner.vertical_scroll_position {
+        runner.vertical_scroll_position = cursor + 1;
+    } else if cursor + height < runner.vertical_scroll_position {
+        runner.vertical_scroll_position = cursor + height;
+    }
+    runner.follow = runner.vertical_scroll_position >= runner.vertical_scroll_size;
+    app.selection = Some(Selection {
+        cursor,
+        ..selection
+    });
+}
+
+/// Makes the first runner of the group active, which also switches the tab in tabs layout
+fn select_group(app: &mut App, group_index: usize) {
+    if let Some(runner_index) = app.groups.get(group_index).and_then(|g| g.runners.first()) {
+        app.active_runner = *runner_index;
+        app.has_zoomed_runner = false;
+    }
+}
+
+fn cycle_group(app: &mut App, forward: bool) {
+    let group_count = app.groups.len();
+    if group_count == 0 {
+        return;
+    }
+    let group_index = app.group_of(app.active_runner).map_or(0, |(g, _)| g);
+    if forward {
+        select_group(app, (group_index + 1) % group_count);
+    } else {
+        select_group(app, (group_index + group_count - 1) % group_count);
+    }
+}
+
+/// Grows (positive `direction`) or shrinks the active pane inside its group row
+fn resize_active_pane(app: &mut App, direction: i32) {
+    let Some((group_index, _)) = app.group_of(app.active_runner) else {
+        return;
+    };
+    let group_runners = app.groups[group_index].runners.clone();
+    let total: u32 = group_runners
+        .iter()
+        .map(|r| u32::from(app.runners[*r].size))
+        .sum();
+    let active_runner = &mut app.runners[app.active_runner];
+    active_runner.size = resize_weight(active_runner.size, total, direction);
+}
+
+/// Grows (positive `direction`) or shrinks the group row of the active pane
+fn resize_active_group(app: &mut App, direction: i32) {
+    let Some((group_index, _)) = app.group_of(app.active_runner) else {
+        return;
+    };
+    let total: u32 = app.groups.iter().map(|g| u32::from(g.size)).sum();
+    let group = &mut app.groups[group_index];
+    group.size = resize_weight(group.size, total, direction);
+}
+
+/// Steps a size weight by a tenth of the total so it works for both small config weights
+/// and the cell sized weights left behind by mouse dragging
+fn resize_weight(size: u16, total: u32, direction: i32) -> u16 {
+    let step = u16::try_from(total / 10).unwrap_or(u16::MAX).max(1);
+    if direction > 0 {
+        size.saturating_add(step)
+    } else {
+        size.saturating_sub(step).max(1)
+    }
+}
+
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.resize_drag = if app.has_zoomed_runner {
+                None
+            } else {
+                find_border(app, mouse.column, mouse.row)
+            };
+            if app.resize_drag.is_some() {
+                return;
+            }
+
+            if let Some(runner_index) = sidebar_runner_at(app, mouse.column, mouse.row) {
+                app.active_runner = runner_index;
+                return;
+            }
+
+            let Some(runner_index) = pane_at(app, mouse.column, mouse.row) else {
+                return;
+            };
+            let now = Instant::now();
+            let is_double_click = app.last_click.is_some_and(|(clicked_at, clicked_runner)| {
+                clicked_runner == runner_index && now - clicked_at <= DOUBLE_CLICK_INTERVAL
+            });
+            app.active_runner = runner_index;
+            if is_double_click {
+                app.last_click = None;
+                app.selection = None;
+                app.has_zoomed_runner = !app.has_zoomed_runner;
+                app.zoomed_runner = runner_index;
+                return;
+            }
+            app.last_click = Some((now, runner_index));
+            app.selection = line_at(app, runner_index, mouse.row).map(|line| Selection {
+                runner: runner_index,
+                anchor: line,
+                cursor: line,
+            });
+            app.is_selecting = app.selection.is_some();
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            if let Some(drag) = app.resize_drag {
+                drag_border(app, drag, mouse.column, mouse.row);
+            } else if app.is_selecting {
+                let Some(selection) = app.selection else {
+                    return;
+                };
+                if let Some(line) = line_at(app, selection.runner, mouse.row) {
+                    app.selection = Some(Selection {
+                        cursor: line,
+                        ..selection
+                    });
+                }
+            }
+        }
+        MouseEventKind::Up(MouseButton::Left) => {
+            app.resize_drag = None;
+            if app.is_selecting {
+                app.is_selecting = false;
+                match app.selection {
+                    Some(selection) if selection.anchor != selection.cursor => {
+                        copy_selection(app, &selection, app.show_timestamps);
+                    }
+                    _ => app.selection = None,
+                }
+            }
+        }
+        MouseEventKind::ScrollDown => {
+            if let Some(runner_index) = pane_at(app, mouse.column, mouse.row) {
+                scroll_runner(app, runner_index, MOUSE_SCROLL_LINES);
+            }
+        }
+        MouseEventKind::ScrollUp => {
+            if let Some(runner_index) = pane_at(app, mouse.column, mouse.row) {
+                scroll_runner(app, runner_index, -MOUSE_SCROLL_LINES);
+            }
+        }
+        _ => (),
+    }
+}
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const MOUSE_SCROLL_LINES: isize = 3;
+const HORIZONTAL_SCROLL_COLUMNS: isize = 4;
+
+/// Moves the bottom of the visible window of a runner by `delta` lines, the runner follows its
+/// output again once the view reaches the end
+fn scroll_runner(app: &mut App, runner_index: usize, delta: isize) {
+    let top = pane_area(app, runner_index).map_or(0, |area| {
+        top_scroll_position(
+            &app.runners[runner_index],
+            area.inner(Margin::new(1, 1)),
+            app.show_timestamps,
+        )
+    });
+    let runner = &mut app.runners[runner_index];
+    let bottom = runner.shown_lines().end;
+    runner.vertical_scroll_position = runner
+        .vertical_scroll_position
+        .max(top)
+        .saturating_add_signed(delta)
+        .clamp(top.min(bottom), bottom);
+    runner.follow = runner.vertical_scroll_position >= bottom;
+}
+
+/// Lowest scroll position that still changes the view, anything below it shows the first lines
+fn top_scroll_position(runner: &Runner, inner: Rect, show_timestamps: bool) -> usize {
+    let height = usize::from(inner.height);
+    let shown = runner.shown_lines();
+    if !runner.wrap {
+        return (shown.start + height).min(shown.end);
+    }
+
+    let mut rows = 0;
+    let mut end = shown.start;
+    while end < shown.end && rows < height {
+        rows += line_rows(&runner.lines[end], inner.width, show_timestamps);
+        end += 1;
+    }
+    // A partly shown last line has to stay below the view so the first line is shown from its start
+    if rows > height {
+        end - 1
+    } else {
+        end
+    }
+}
+
+/// Count of lines that fit into the runner's pane
+fn page_size(app: &App, runner_index: usize) -> isize {
+    pane_area(app, runner_index).map_or(1, |area| area.height.saturating_sub(2).max(1) as isize)
+}
+
+/// Moves the dragged border so it follows the mouse cursor
+fn drag_border(app: &mut App, drag: ResizeDrag, column: u16, row: u16) {
+    match drag {
+        ResizeDrag::Horizontal { group, position } => {
+            let runners = app.groups[group].runners.clone();
+            let (Some(left), Some(right)) = (
+                pane_area(app, runners[position]),
+                pane_area(app, runners[position + 1]),
+            ) else {
+                return;
+            };
+            // Weights are switched to cell widths so the border follows the cursor exactly
+            for runner_index in runners.iter() {
+                if let Some(area) = pane_area(app, *runner_index) {
+                    app.runners[*runner_index].size = area.width.max(1);
+                }
+            }
+            let total = left.width + right.width;
+            let left_width = (column + 1)
+                .saturating_sub(left.x)
+                .clamp(1, total.saturating_sub(1).max(1));
+            app.runners[runners[position]].size = left_width.max(1);
+            app.runners[runners[position + 1]].size = total.saturating_sub(left_width).max(1);
+        }
+        ResizeDrag::Vertical { group } => {
+            let (Some(upper), Some(lower)) = (group_area(app, group), group_area(app, group + 1))
+            else {
+                return;
+            };
+            for group_index in 0..app.groups.len() {
+                if let Some(area) = group_area(app, group_index) {
+                    app.groups[group_index].size = area.height.max(1);
+                }
+            }
+            let total = upper.height + lower.height;
+            let upper_height = (row + 1)
+                .saturating_sub(upper.y)
+                .clamp(1, total.saturating_sub(1).max(1));
+            app.groups[group].size = upper_height.max(1);
+            app.groups[group + 1].size = total.saturating_sub(upper_height).max(1);
+        }
+    }
+}
+
+/// Finds the runner pane under the given cell
+fn pane_at(app: &App, column: u16, row: u16) -> Option<usize> {
+    app.pane_areas
+        .iter()
+        .find(|(_, area)| area.contains(Position::new(column, row)))
+        .map(|(runner_index, _)| *runner_index)
+}
+
+/// Finds the runner listed in the sidebar under the given cell
+fn sidebar_runner_at(app: &App, column: u16, row: u16) -> Option<usize> {
+    let area = app.sidebar_area?;
+    let inner = area.inner(Margin::new(1, 1));
+    if !inner.contains(Position::new(column, row)) {
+        return None;
+    }
+    let runner_index = app.sidebar_offset + usize::from(row - inner.y);
+    (runner_index < app.runners.len()).then_some(runner_index)
+}
+
+/// Finds the index of the runner's line shown on the given screen row
+fn line_at(app: &App, runner_index: usize, row: u16) -> Option<usize> {
+    let inner = pane_area(app, runner_index)?.inner(Margin::new(1, 1));
+    let runner = &app.runners[runner_index];
+    let view = pane_view(runner, inner, app.show_timestamps);
+    if view.lines.is_empty() {
+        return None;
+    }
+    let mut offset = usize::from(row.clamp(inner.y, inner.bottom().saturating_sub(1)) - inner.y);
+    if !runner.wrap {
+        return Some((view.lines.start + offset).min(view.lines.end - 1));
+    }
+
+    offset += view.skipped_rows;
+    for line_index in view.lines.clone() {
+        let rows = line_rows(&runner.lines[line_index], inner.width, app.show_timestamps);
+        if offset < rows {
+            return Some(line_index);
+        }
+        offset -= rows;
+    }
+    Some(view.lines.end - 1)
+}
+
+/// Lines of a runner that fit into a pane
+struct PaneView {
+    lines: Range<usize>,
+    /// Rows of the first wrapped line that are scrolled above the top of the pane
+    skipped_rows: usize,
+}
+
+/// Finds the lines that fit into a pane with the given inner area, ending at the scroll position
+fn pane_view(runner: &Runner, inner: Rect, show_timestamps: bool) -> PaneView {
+    let height = usize::from(inner.height);
+    let shown = runner.shown_lines();
+    let bottom = runner
+        .vertical_scroll_position
+        .clamp(shown.start, shown.end);
+    if !runner.wrap {
+        let start = bottom.saturating_sub(height).max(shown.start);
+        return PaneView {
+            lines: start..(start + height).min(shown.end),
+            skipped_rows: 0,
+        };
+    }
+
+    let mut end = bottom;
+    let mut start = end;
+    let mut rows = 0;
+    while start > shown.start && rows < height {
+        start -= 1;
+        rows += line_rows(&runner.lines[start], inner.width, show_timestamps);
+    }
+    if rows >= height {
+        return PaneView {
+            lines: start..end,
+            skipped_rows: rows - height,
+        };
+    }
+    // The start of the output is reached, so the pane is filled from the top instead
+    while end < shown.end && rows < height {
+        rows += line_rows(&runner.lines[end], inner.width, show_timestamps);
+        end += 1;
+    }
+    PaneView {
+        lines: start..end,
+        skipped_rows: 0,
+    }
+}
+
+/// Count of rows a line takes when it is wrapped to the given width
+fn line_rows(line: &StdLine, width: u16, show_timestamps: bool) -> usize {
+    Paragraph::new(line.to_string(show_timestamps))
+        .wrap(Wrap { trim: false })
+        .line_count(width)
+        .max(1)
+}
+
+/// Moves the horizontal scroll of the active runner, stopping when the longest line is fully shown
+fn scroll_active_runner_horizontally(app: &mut App, delta: isize) {
+    let width = pane_area(app, app.active_runner)
+        .map_or(0, |area| usize::from(area.width.saturating_sub(2)));
+    let show_timestamps = app.show_timestamps;
+    let runner = &mut app.runners[app.active_runner];
+    if runner.wrap {
+        return;
+    }
+    let max_width = runner
+        .lines
+        .iter()
+        .map(|l| Line::from(l.to_string(show_timestamps)).width())
+        .max()
+        .unwrap_or(0);
+    runner.horizontal_scroll_position = runner
+        .horizontal_scroll_position
+        .saturating_add_signed(delta)
+        .min(max_width.saturating_sub(width));
+}
+
+fn copy_selection(app: &mut App, selection: &Selection, show_timestamp: bool) {
+    match clipboard::copy(&selection_text(app, selection, show_timestamp)) {
+        Ok(()) => app.notify(format!("Copied {} lines", selection.lines().len())),
+        Err(err) => {
+            log_debug(app, LogLevel::Error, format!("Copying failed: {err}"));
+            app.notify(format!("Copy failed: {err}"));
+        }
+    }
+}
+
+fn selection_text(app: &App, selection: &Selection, show_timestamp: bool) -> String {
+    let runner = &app.runners[selection.runner];
+    let lines = selection.lines();
+    runner.lines[lines.start.min(runner.lines.len())..lines.end.min(runner.lines.len())]
+        .iter()
+        .map(|l| l.to_string(show_timestamp))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Finds the pane or group border under the given cell
+fn find_border(app: &App, column: u16, row: u16) -> Option<ResizeDrag> {
+    for (group_index, group) in app.groups.iter().enumerate() {
+        for position in 0..group.runners.len().saturating_sub(1) {
+            let Some(area) = pane_area(app, group.runners[position]) else {
+                continue;
+            };
+            let on_border = column + 1 == area.right() || column == area.right();
+            if on_border && row >= area.y && row < area.bottom() {
+                return Some(ResizeDrag::Horizontal {
+                    group: group_index,
+                    position,
+                });
+            }
+        }
+    }
+    for group_index in 0..app.groups.len().saturating_sub(1) {
+        let Some(area) = group_area(app, group_index) else {
+            continue;
+        };
+        let on_border = row + 1 == area.bottom() || row == area.bottom();
+        if on_border && column >= area.x && column < area.right() {
+            return Some(ResizeDrag::Vertical { group: group_index });
+        }
+    }
+    None
+}
+
+fn pane_area(app: &App, runner_index: usize) -> Option<Rect> {
+    app.pane_areas
+        .iter()
+        .find(|(index, _)| *index == runner_index)
+        .map(|(_, area)| *area)
+}
+
+fn group_area(app: &App, group_index: usize) -> Option<Rect> {
+    app.group_areas
+        .iter()
+        .find(|(index, _)| *index == group_index)
+        .map(|(_, area)| *area)
+}
+
 pub fn ui(frame: &mut Frame, app: &mut Arc<Mutex<App>>) {
-    let app = app.lock().unwrap();
+    let mut app = app.lock().unwrap();
 
     let [main_area_width, debug_area_width] = if app.show_debug { [80, 20] } else { [100, 0] };
     let [main_area, debug_area] = Layout::horizontal(vec![
@@ -138,106 +929,398 @@ pub fn ui(frame: &mut Frame, app: &mut Arc<Mutex<App>>) {
     ])
     .areas(frame.area());
 
-    let mut constraints = vec![Constraint::Length(1)];
+    let [title_area, body_area, status_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(main_area);
+
+    frame.render_widget(Block::bordered().title("Control panel"), title_area);
+
+    if app.mode == Mode::Export {
+        render_prompt(
+            &app,
+            "Export to (.log, .jsonl, .html): ",
+            status_area,
+            frame,
+        );
+    } else if app.mode == Mode::DebugFilter {
+        render_prompt(&app, "Filter debug log: ", status_area, frame);
+    } else {
+        if app
+            .status_message
+            .as_ref()
+            .is_some_and(|(_, shown_at)| shown_at.elapsed() >= STATUS_MESSAGE_DURATION)
+        {
+            app.status_message = None;
+        }
+        render_status_bar(&app, status_area, frame);
+    }
+
+    let mut pane_areas = vec![];
+    let mut group_areas = vec![];
+    let mut sidebar_area = None;
     if app.has_zoomed_runner {
-        constraints.push(Constraint::Min(0));
+        render_runner_pane(&app, app.zoomed_runner, body_area, frame);
+        pane_areas.push((app.zoomed_runner, body_area));
     } else {
-        constraints.extend(app.groups.iter().map(|_g| Constraint::Min(0)).into_iter());
+        match app.layout {
+            LayoutMode::Grid => {
+                let group_rows =
+                    Layout::vertical(app.groups.iter().map(|g| Constraint::Fill(g.size)))
+                        .split(body_area);
+
+                for (group_index, group_row) in group_rows.iter().enumerate() {
+                    render_group_row(&app, group_index, *group_row, frame, &mut pane_areas);
+                    group_areas.push((group_index, *group_row));
+                }
+            }
+            LayoutMode::Tabs => {
+                let [tabs_area, group_row] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(body_area);
+                let active_group = app.group_of(app.active_runner).map_or(0, |(g, _)| g);
+                render_tabs(&app, active_group, tabs_area, frame);
+                render_group_row(&app, active_group, group_row, frame, &mut pane_areas);
+                group_areas.push((active_group, group_row));
+            }
+            LayoutMode::Sidebar => {
+                let [list_area, pane_area] =
+                    Layout::horizontal([Constraint::Length(SIDEBAR_WIDTH), Constraint::Fill(1)])
+                        .areas(body_area);
+                app.sidebar_offset = render_sidebar(&app, list_area, frame);
+                sidebar_area = Some(list_area);
+                render_runner_pane(&app, app.active_runner, pane_area, frame);
+                pane_areas.push((app.active_runner, pane_area));
+            }
+        }
     }
-    constraints.push(Constraint::Length(1));
+    for (runner_index, _) in pane_areas.iter() {
+        let runner = &mut app.runners[*runner_index];
+        runner.seen_lines = runner.lines.len();
+    }
+    app.pane_areas = pane_areas;
+    app.group_areas = group_areas;
+    app.sidebar_area = sidebar_area;
 
-    let left_areas = Layout::vertical(constraints).split(main_area);
+    if app.show_help {
+        let mut help_lines: Vec<Line> = app
+            .keymap
+            .help()
+            .into_iter()
+            .map(|(keys, description)| Line::from(format!("{keys} - {description}")))
+            .collect();
+        help_lines.extend([
+            Line::from("Drag a pane border with the mouse to resize it"),
+            Line::from("Click a pane to focus it, double-click to zoom it"),
+            Line::from("Scroll a pane with the mouse wheel"),
+            Line::from("Drag over lines to select and copy them"),
+        ]);
 
-    let title_area = left_areas[0];
-    frame.render_widget(Block::bordered().title("Control panel"), title_area);
+        let area = popup_area(frame.area(), 80, 80);
+        let popup_block = Block::bordered()
+            .title("Keybindings")
+            .border_style(app.theme.popup_border)
+            .style(app.theme.popup);
+        let inner = popup_block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup_block, area);
+
+        // The bindings are split into columns when they don't fit the height of the popup
+        let rows = usize::from(inner.height).max(1);
+        let columns = help_lines.len().div_ceil(rows).max(1);
+        let column_areas = Layout::horizontal(vec![Constraint::Fill(1); columns]).split(inner);
+        let mut help_lines = help_lines.into_iter();
+        for column_area in column_areas.iter() {
+            let column: Vec<Line> = help_lines.by_ref().take(rows).collect();
+            frame.render_widget(Paragraph::new(column), *column_area);
+        }
+    }
+
+    if app.show_details {
+        render_details(&app, frame);
+    }
 
-    let status_area = left_areas[left_areas.len() - 1];
+    if app.show_debug {
+        render_debug_pane(&app, debug_area, frame);
+    }
+}
+
+/// Renders the latest debug log lines that match the level and the filter
+fn render_debug_pane(app: &MutexGuard<App>, area: Rect, frame: &mut Frame) {
+    let filter = app.debug_filter.to_lowercase();
+    let lines: Vec<&LogLine> = app
+        .debug_lines
+        .iter()
+        .filter(|l| l.level >= app.debug_level)
+        .filter(|l| filter.is_empty() || l.message.to_lowercase().contains(&filter))
+        .collect();
+
+    let mut title = format!("Debug Logs [{}+]", app.debug_level.label());
+    if !app.debug_filter.is_empty() {
+        title += &format!(" [/{}]", app.debug_filter);
+    }
+    let block = Block::bordered()
+        .title(title)
+        .border_style(app.theme.popup_border)
+        .style(app.theme.popup);
+    let inner = block.inner(area);
+
+    // Lines are wrapped, so the rows are counted from the bottom to fill the pane
+    let end = lines.len().saturating_sub(app.debug_scroll);
+    let mut start = end;
+    let mut rows = 0;
+    while start > 0 && rows < usize::from(inner.height) {
+        start -= 1;
+        rows += Paragraph::new(lines[start].to_string())
+            .wrap(Wrap { trim: false })
+            .line_count(inner.width)
+            .max(1);
+    }
+    let skipped_rows = rows.saturating_sub(usize::from(inner.height));
+
+    let mut block = block;
+    if app.debug_scroll > 0 {
+        block = block
+            .title_bottom(Line::from(format!(" ▼ {} more ", lines.len() - end)).right_aligned());
+    }
     frame.render_widget(
-        Block::bordered().title("Use <N> to scroll panes, <R> to restart process, <?> for all key-bindings, <Q> to exit")
-        ,status_area
+        Paragraph::new(
+            lines[start..end]
+                .iter()
+                .map(|l| l.to_ratatui_line(&app.theme))
+                .collect::<Vec<Line>>(),
+        )
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((skipped_rows.try_into().unwrap_or(u16::MAX), 0)),
+        area,
     );
+}
 
-    let main_areas = &left_areas[1..left_areas.len() - 1];
+/// Renders the panes of a single group next to each other
+fn render_group_row(
+    app: &MutexGuard<App>,
+    group_index: usize,
+    area: Rect,
+    frame: &mut Frame,
+    pane_areas: &mut Vec<(usize, Rect)>,
+) {
+    let group = &app.groups[group_index];
+    let row_area = Layout::horizontal(
+        group
+            .runners
+            .iter()
+            .map(|r| Constraint::Fill(app.runners[*r].size)),
+    )
+    .split(area);
+
+    for (runner_iter_index, runner_index) in group.runners.iter().enumerate() {
+        let area = row_area[runner_iter_index];
+        render_runner_pane(app, *runner_index, area, frame);
+        pane_areas.push((*runner_index, area));
+    }
+}
 
-    let mut area_group_index = 0;
-    let areas: Vec<_> = main_areas
+/// Renders one tab per group, marking groups that have a runner in error state
+fn render_tabs(app: &MutexGuard<App>, active_group: usize, area: Rect, frame: &mut Frame) {
+    let titles: Vec<Line> = app
+        .groups
         .iter()
-        .map(|main_area| {
-            let inner_areas = if app.has_zoomed_runner {
-                1
-            } else {
-                app.groups[area_group_index].runners.len()
-            };
+        .enumerate()
+        .map(|(group_index, group)| {
+            let active = group
+                .runners
+                .iter()
+                .filter(|r| matches!(app.runners[**r].state, RunnerState::Active))
+                .count();
+            let has_error = group
+                .runners
+                .iter()
+                .any(|r| matches!(app.runners[*r].state, RunnerState::Error));
 
-            let a = Layout::horizontal(
-                iter::repeat(Constraint::Percentage(100))
-                    .take(inner_areas)
-                    .collect::<Vec<Constraint>>(),
-            )
-            .split(main_area.to_owned());
-            area_group_index += 1;
-            a
+            let mut spans = vec![];
+            if has_error {
+                spans.push(Span::styled("● ", app.theme.error));
+            }
+            spans.push(Span::raw(format!(
+                "{} {} ({}/{})",
+                group_index + 1,
+                group.name,
+                active,
+                group.runners.len()
+            )));
+            Line::from(spans)
         })
         .collect();
 
-    if app.has_zoomed_runner {
-        let row_area = &areas[0];
-        let area = row_area[0];
-        render_runner_pane(&app, app.zoomed_runner, area, frame)
-    } else {
-        for (group_index, group) in app.groups.iter().enumerate() {
-            let row_area = &areas[group_index];
+    frame.render_widget(
+        Tabs::new(titles)
+            .select(active_group)
+            .highlight_style(Style::new().bold().reversed()),
+        area,
+    );
+}
 
-            for (runner_iter_index, runner_index) in group.runners.iter().enumerate() {
-                let area = row_area[runner_iter_index];
-                render_runner_pane(&app, *runner_index, area, frame);
-            }
+const STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(4);
+
+/// Renders the mode, runner counts per state and details of the active runner on the left, and
+/// the latest message or the key hints on the right
+fn render_status_bar(app: &MutexGuard<App>, area: Rect, frame: &mut Frame) {
+    let mode = match app.mode {
+        Mode::Normal => " NORMAL ",
+        Mode::Visual => " VISUAL ",
+        Mode::Export => " EXPORT ",
+        Mode::DebugFilter => " FILTER ",
+    };
+    let mut spans = vec![Span::raw(mode).reversed(), Span::raw(" ")];
+
+    for state in [
+        RunnerState::Active,
+        RunnerState::Error,
+        RunnerState::Finish,
+        RunnerState::Ready,
+    ] {
+        let count = app.runners.iter().filter(|r| r.state == state).count();
+        if count > 0 {
+            spans.push(Span::styled(
+                format!("● {count} {} ", state.label()),
+                app.theme.state(&state),
+            ));
         }
     }
 
-    if app.show_help {
-        let popup_block = Paragraph::new(vec![
-            Line::from("<N> - Next pane"),
-            Line::from("<P> - Previous pane"),
-            Line::from("<Z> - Zoom out/in pane"),
-            Line::from("<T> - Toggle timestamps"),
-            Line::from("<S> - Stop process in the active pane"),
-            Line::from("<R> - Restart process in the active pane"),
-            Line::from("<Q> - Quit app"),
-            Line::from("<D> - Toggle debug window"),
-        ])
-        .block(
-            Block::bordered()
-                .title("Keybindings")
-                .border_style(Style::new().green())
-                .style(Style::default().bg(Color::Black)),
-        );
+    if let Some(r) = app.runners.get(app.active_runner) {
+        let mut details = vec![r.name.clone()];
+        if let Some(pid) = r.pid {
+            details.push(format!("pid {pid}"));
+        }
+        if let (RunnerState::Active, Some(started_at)) = (&r.state, r.started_at) {
+            details.push(format!("up {}", format_duration(Utc::now() - started_at)));
+        }
+        if r.restart_count > 0 {
+            details.push(format!("restarts {}", r.restart_count));
+        }
+        if let Some(status) = &r.exit_status {
+            details.push(format!("exit {}", format_exit(status)));
+        }
+        spans.push(Span::raw(format!("| {}", details.join(" · "))));
+    }
 
-        let area = popup_area(frame.area(), 60, 60);
-        frame.render_widget(Clear, area);
-        frame.render_widget(popup_block, area)
+    let right = match &app.status_message {
+        Some((message, _)) => Span::raw(message.clone()).bold(),
+        None => Span::raw(format!(
+            "{} next pane, {} restart, {} help, {} quit",
+            app.keymap.keys_for(Action::NextPane),
+            app.keymap.keys_for(Action::Restart),
+            app.keymap.keys_for(Action::Help),
+            app.keymap.keys_for(Action::Quit),
+        ))
+        .dim(),
+    };
+
+    let [left_area, right_area] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(right.width().try_into().unwrap_or(u16::MAX)),
+    ])
+    .spacing(1)
+    .areas(area);
+    frame.render_widget(Paragraph::new(Line::from(spans)), left_area);
+    frame.render_widget(Paragraph::new(Line::from(right)), right_area);
+}
+
+/// Renders a single line text input with the cursor at its end
+fn render_prompt(app: &MutexGuard<App>, label: &str, area: Rect, frame: &mut Frame) {
+    let mut spans = vec![Span::raw(label).bold(), Span::raw(app.input.clone())];
+    if let Some(err) = &app.input_error {
+        spans.push(Span::styled(format!("  {err}"), app.theme.error));
     }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
 
-    if app.show_debug {
-        frame.render_widget(
-            Paragraph::new(
-                app.debug_lines
-                    .clone()
-                    .iter()
-                    .map(|l| l.to_ratatui_line(false))
-                    .collect::<Vec<Line>>(),
-            )
-            .block(
-                Block::bordered()
-                    .title("Debug Logs")
-                    .border_style(Style::new().green())
-                    .style(Style::default().bg(Color::Black)),
-            ),
-            debug_area,
-        )
+    let cursor_x = area.x + (label.chars().count() + app.input.chars().count()) as u16;
+    frame.set_cursor_position(Position::new(
+        cursor_x.min(area.right().saturating_sub(1)),
+        area.y,
+    ));
+}
+
+const SIDEBAR_WIDTH: u16 = 36;
+
+/// Renders the list of all runners with their state, uptime and count of unread lines, scrolled
+/// so the active runner is visible. Returns the index of the first runner shown
+fn render_sidebar(app: &MutexGuard<App>, area: Rect, frame: &mut Frame) -> usize {
+    let items: Vec<ListItem> = app
+        .runners
+        .iter()
+        .enumerate()
+        .map(|(runner_index, r)| {
+            let marker = if app.active_runner == runner_index {
+                " > "
+            } else {
+                "   "
+            };
+            let uptime = match (&r.state, r.started_at) {
+                (RunnerState::Active, Some(started_at)) => format_duration(Utc::now() - started_at),
+                _ => String::new(),
+            };
+            let unread = r.lines.len().saturating_sub(r.seen_lines);
+            let badge = if unread > 0 {
+                format!(" [{unread}]")
+            } else {
+                String::new()
+            };
+
+            let mut style = app.theme.state(&r.state);
+            if app.active_runner == runner_index {
+                style = style.bold().reversed();
+            }
+            ListItem::new(Line::from(vec![
+                Span::raw(marker),
+                Span::raw(r.name.clone()),
+                Span::raw(" "),
+                Span::raw(uptime).dim(),
+                Span::styled(badge, app.theme.badge),
+            ]))
+            .style(style)
+        })
+        .collect();
+
+    let mut state = ListState::default()
+        .with_offset(app.sidebar_offset)
+        .with_selected(Some(app.active_runner));
+    frame.render_stateful_widget(
+        List::new(items).block(Block::bordered().title("Runners")),
+        area,
+        &mut state,
+    );
+    state.offset()
+}
+
+/// Formats a duration in a compact form, e.g. `1h02m`, `3m05s` or `12s`
+fn format_duration(duration: chrono::Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    if seconds >= 3600 {
+        format!("{}h{:02}m", seconds / 3600, (seconds % 3600) / 60)
+    } else if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{seconds}s")
     }
 }
 
+/// Summary of the resource usage with sparklines of the recent CPU and memory usage
+fn format_metrics(metrics: &ProcessMetrics) -> String {
+    format!(
+        " cpu {:.0}% {} · rss {} {} · thr {} · fd {} ",
+        metrics.cpu_percent,
+        sparkline(metrics.cpu_history.iter().copied()),
+        format_bytes(metrics.rss_bytes),
+        sparkline(metrics.rss_history.iter().map(|&b| b as f64)),
+        metrics.threads,
+        metrics.open_files
+    )
+}
+
 fn render_runner_pane(app: &MutexGuard<App>, runner_index: usize, area: Rect, frame: &mut Frame) {
     let r = &app.runners[runner_index];
 
@@ -245,51 +1328,76 @@ fn render_runner_pane(app: &MutexGuard<App>, runner_index: usize, area: Rect, fr
     if app.active_runner == runner_index {
         title = " > ".to_owned() + &title;
     }
+    if let Some(selection) = app.selection.filter(|s| s.runner == runner_index) {
+        if app.mode == Mode::Visual {
+            title += &format!(" [VISUAL {} lines]", selection.lines().len());
+        }
+    }
+    if let Some(run) = r.shown_run {
+        title += &format!(" [run {}/{}]", run + 1, r.runs.len());
+    }
 
-    let style = match r.state {
-        RunnerState::Ready => Style::new().blue(),
-        RunnerState::Error => Style::new().red(),
-        RunnerState::Active => Style::new().gray(),
-        RunnerState::Finish => Style::new().green(),
-    };
+    let mut style = app.theme.state(&r.state);
+    let mut border_type = BorderType::Plain;
+    if app.active_runner == runner_index {
+        style = style.patch(app.theme.active_border);
+        border_type = BorderType::Thick;
+    }
 
-    let mut scroll: usize = r.vertical_scroll_position.try_into().unwrap();
-    let mut scroll_size: usize = r.vertical_scroll_size.try_into().unwrap();
-    let height: usize = area.height.try_into().unwrap();
-    scroll = scroll.saturating_sub(height);
-    scroll_size = scroll_size.saturating_sub(height);
-
-    let mut lines: Vec<Line> = Vec::new();
-    let empty_height = r.vertical_scroll_position.saturating_sub(height);
-    lines.append(&mut vec![Line::from(""); empty_height]);
-    lines.append(
-        &mut r.lines[empty_height..r.vertical_scroll_position]
-            .iter()
-            .map(|x| x.to_ratatui_line(app.show_timestamps))
-            .collect::<Vec<Line>>(),
-    );
-    lines.append(&mut vec![
-        Line::from("");
-        r.vertical_scroll_size.saturating_sub(empty_height)
-    ]);
+    let inner = area.inner(Margin::new(1, 1));
+    let height = usize::from(inner.height);
+    let view = pane_view(r, inner, app.show_timestamps);
+    let visible = view.lines.clone();
+    let selected = app
+        .selection
+        .filter(|selection| selection.runner == runner_index)
+        .map(|selection| selection.lines());
 
-    frame.render_widget(
-        Paragraph::new(
-            lines, // r.lines
-                  //     .clone()
-                  //     .iter()
-                  //     .map(|l| l.to_ratatui_line(app.show_timestamps))
-                  //     .collect::<Vec<Line>>(),
-        )
-        .scroll((scroll.try_into().unwrap(), 0))
-        .block(Block::bordered().border_style(style).title(title)),
-        area,
-    );
+    let lines: Vec<Line> = r.lines[visible.clone()]
+        .iter()
+        .enumerate()
+        .map(|(offset, x)| {
+            let line = x.to_ratatui_line(app.show_timestamps, &app.theme);
+            match &selected {
+                Some(selected) if selected.contains(&(visible.start + offset)) => {
+                    line.patch_style(app.theme.highlight)
+                }
+                _ => line,
+            }
+        })
+        .collect();
+    let max_width = lines.iter().map(|l| l.width()).max().unwrap_or(0);
+
+    let mut block = Block::bordered()
+        .border_type(border_type)
+        .border_style(style)
+        .title(title);
+    let shown = r.shown_lines();
+    let below = shown.end.saturating_sub(visible.end);
+    if !r.follow && below > 0 {
+        block = block.title_bottom(Line::from(format!(" ▼ {below} new lines ")).right_aligned());
+    }
+    if let Some(metrics) = r.metrics.as_ref().filter(|_| app.show_metrics) {
+        block = block.title_bottom(format_metrics(metrics));
+    }
+    let mut paragraph = Paragraph::new(lines).block(block);
+    if r.wrap {
+        paragraph = paragraph
+            .wrap(Wrap { trim: false })
+            .scroll((view.skipped_rows.try_into().unwrap_or(u16::MAX), 0));
+    } else {
+        paragraph = paragraph.scroll((
+            0,
+            r.horizontal_scroll_position.try_into().unwrap_or(u16::MAX),
+        ));
+    }
+    frame.render_widget(paragraph, area);
 
     let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
         .begin_symbol(Some("↑"))
         .end_symbol(Some("↓"));
-    let mut scrollbar_state = ScrollbarState::new(scroll_size).position(scroll);
+    let mut scrollbar_state = ScrollbarState::new(shown.len().saturating_sub(height))
+        .position(visible.start - shown.start);
 
     frame.render_stateful_widget(
         scrollbar,
@@ -300,6 +1408,201 @@ fn render_runner_pane(app: &MutexGuard<App>, runner_index: usize, area: Rect, fr
         }),
         &mut scrollbar_state,
     );
+
+    let inner_width = usize::from(inner.width);
+    if !r.wrap && (max_width > inner_width || r.horizontal_scroll_position > 0) {
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::HorizontalBottom)
+            .begin_symbol(Some("←"))
+            .end_symbol(Some("→"));
+        let mut scrollbar_state = ScrollbarState::new(max_width.saturating_sub(inner_width))
+            .position(r.horizontal_scroll_position);
+
+        frame.render_stateful_widget(
+            scrollbar,
+            area.inner(Margin {
+                vertical: 0,
+                horizontal: 1,
+            }),
+            &mut scrollbar_state,
+        );
+    }
+}
+
+/// Renders everything known about the active runner's config and process in a popup
+fn render_details(app: &MutexGuard<App>, frame: &mut Frame) {
+    let r = &app.runners[app.active_runner];
+    let label = |name: &str| Span::styled(format!("{name:<18}"), app.theme.timestamp);
+    let row = |name: &str, value: String| Line::from(vec![label(name), Span::raw(value)]);
+
+    let dir = if r.config.dir.is_empty() {
+        env::current_dir().unwrap_or_default()
+    } else {
+        fs::canonicalize(&r.config.dir).unwrap_or_else(|_| r.config.dir.clone().into())
+    };
+    // The runner can come from an include, and includes or the local override can change it
+    let mut files = r.config.files.iter();
+    let defined_in = files.next().unwrap_or(&app.config_path);
+    let overridden_in: Vec<String> = files.map(|f| f.display().to_string()).collect();
+
+    let mut lines = vec![
+        row("Command", shell_words(&r.config.args)),
+        row("Directory", dir.display().to_string()),
+        row("Config file", defined_in.display().to_string()),
+    ];
+    if !overridden_in.is_empty() {
+        lines.push(row("Overridden in", overridden_in.join(", ")));
+    }
+    lines.push(row("State", r.state.label().into()));
+
+    match r.pid {
+        Some(pid) => {
+            // SAFETY: getpgid only queries the process table
+            let pgid = unsafe { libc::getpgid(pid as libc::pid_t) };
+            lines.push(row("PID", pid.to_string()));
+            if pgid >= 0 {
+                lines.push(row("PGID", pgid.to_string()));
+            }
+        }
+        None => lines.push(row("PID", "-".into())),
+    }
+    if let Some(started_at) = r.started_at {
+        lines.push(row(
+            "Started",
+            started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        ));
+        if r.state == RunnerState::Active {
+            lines.push(row("Uptime", format_duration(Utc::now() - started_at)));
+        }
+    }
+    lines.push(row("Restarts", r.restart_count.to_string()));
+    lines.push(row(
+        "Auto start",
+        format!(
+            "{}, restart on finish: {}",
+            r.config.auto_start, r.config.restart_on_finish
+        ),
+    ));
+
+    let limits = &r.config.limits;
+    let mut limit_values = vec![];
+    if let Some(size) = limits.address_space {
+        limit_values.push(format!("address space {}", format_bytes(size.0)));
+    }
+    if let Some(seconds) = limits.cpu_seconds {
+        limit_values.push(format!("cpu {seconds}s"));
+    }
+    if let Some(files) = limits.open_files {
+        limit_values.push(format!("files {files}"));
+    }
+    if let Some(size) = limits.core_size {
+        limit_values.push(format!("core {}", format_bytes(size.0)));
+    }
+    if let Some(size) = limits.restart_above_rss {
+        limit_values.push(format!("restart above rss {}", format_bytes(size.0)));
+    }
+    if !limit_values.is_empty() {
+        lines.push(row("Limits", limit_values.join(", ")));
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::from("Runs").bold());
+    if r.runs.is_empty() {
+        lines.push(Line::from("  none").dim());
+    }
+    for (run_index, run) in r.runs.iter().enumerate().rev() {
+        let ended = match (run.ended_at, run.exit_status) {
+            (Some(ended_at), Some(status)) => format!(
+                "ended {} with {}",
+                ended_at.with_timezone(&Local).format("%H:%M:%S"),
+                format_exit(&status)
+            ),
+            (Some(ended_at), None) => {
+                format!(
+                    "ended {}",
+                    ended_at.with_timezone(&Local).format("%H:%M:%S")
+                )
+            }
+            (None, _) => "running".into(),
+        };
+        lines.push(Line::from(format!(
+            "  #{:<3} {}  {:<16} {ended}",
+            run_index + 1,
+            run.started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            run.trigger.label(),
+        )));
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::from("Environment").bold());
+    let mut vars: HashMap<String, String> = env::vars().collect();
+    vars.extend(r.config.env.clone());
+    let mut vars: Vec<(String, String)> = vars.into_iter().collect();
+    vars.sort();
+    lines.extend(
+        vars.into_iter()
+            .map(|(name, value)| Line::from(format!("  {name}={}", mask_secret(&name, value)))),
+    );
+
+    let area = popup_area(frame.area(), 80, 80);
+    let block = Block::bordered()
+        .title(format!("Details of {}", r.name))
+        .title_bottom(Line::from(format!(
+            " {}/{} scroll, {} close ",
+            app.keymap.keys_for(Action::ScrollDown),
+            app.keymap.keys_for(Action::ScrollUp),
+            app.keymap.keys_for(Action::Cancel),
+        )))
+        .border_style(app.theme.popup_border)
+        .style(app.theme.popup);
+    let scroll = app
+        .details_scroll
+        .min(lines.len().saturating_sub(1))
+        .try_into()
+        .unwrap_or(u16::MAX);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0)),
+        area,
+    );
+}
+
+/// Hides the values of variables whose names suggest they hold credentials
+fn mask_secret(name: &str, value: String) -> String {
+    let name = name.to_uppercase();
+    if ["KEY", "TOKEN", "SECRET", "PASSWORD"]
+        .iter()
+        .any(|word| name.contains(word))
+    {
+        "********".into()
+    } else {
+        value
+    }
+}
+
+/// Joins the arguments into a command line, quoting the ones a shell would split or expand
+pub fn shell_words(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let is_plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "-_./=:,+@%".contains(c));
+            if is_plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
 }
 
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
@@ -310,3 +1613,84 @@ fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
     let [area] = horizontal.areas(area);
     area
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// App with one runner whose pane has an inner area of 10 by 3 cells at row 1
+    fn app_with_lines(lines: &[&str], wrap: bool) -> App {
+        let cfg: RunnerCfg = serde_yaml::from_str("{ name: a, dir: '', args: [x] }").unwrap();
+        let mut runner = Runner::new(0, &cfg);
+        for line in lines {
+            runner
+                .lines
+                .push(StdLine::new(line.to_string(), Stream::Stdout));
+        }
+        runner.wrap = wrap;
+        runner.vertical_scroll_position = lines.len();
+        let mut app = App::default();
+        app.runners.push(runner);
+        app.pane_areas.push((0, Rect::new(0, 0, 12, 5)));
+        app
+    }
+
+    fn inner() -> Rect {
+        Rect::new(1, 1, 10, 3)
+    }
+
+    fn view(app: &App) -> (Range<usize>, usize) {
+        let view = pane_view(&app.runners[0], inner(), false);
+        (view.lines, view.skipped_rows)
+    }
+
+    #[test]
+    fn unwrapped_view_ends_at_the_scroll_position() {
+        let lines: Vec<String> = (0..10).map(|i| format!("line {i}")).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut app = app_with_lines(&lines, false);
+        assert_eq!(view(&app), (7..10, 0));
+        assert_eq!(line_at(&app, 0, 1), Some(7));
+        assert_eq!(line_at(&app, 0, 10), Some(9));
+
+        let top = top_scroll_position(&app.runners[0], inner(), false);
+        assert_eq!(top, 3);
+        app.runners[0].vertical_scroll_position = top;
+        assert_eq!(view(&app), (0..3, 0));
+        assert_eq!(line_at(&app, 0, 2), Some(1));
+    }
+
+    #[test]
+    fn wrapped_view_skips_the_rows_above_the_pane() {
+        let long = "x".repeat(25);
+        let longer = "y".repeat(15);
+        let app = app_with_lines(&["short", &long, &longer], true);
+        // 1 + 3 + 2 rows, so the first row of the long line is above the pane
+        assert_eq!(view(&app), (1..3, 2));
+        assert_eq!(line_at(&app, 0, 1), Some(1));
+        assert_eq!(line_at(&app, 0, 2), Some(2));
+        assert_eq!(line_at(&app, 0, 3), Some(2));
+    }
+
+    #[test]
+    fn wrapped_view_scrolled_to_the_top_starts_with_the_first_line() {
+        let long = "x".repeat(25);
+        let mut app = app_with_lines(&["short", &long, "end"], true);
+        // The long line only fits partly, so it stays below the top scroll position
+        let top = top_scroll_position(&app.runners[0], inner(), false);
+        assert_eq!(top, 1);
+        app.runners[0].vertical_scroll_position = top;
+        assert_eq!(view(&app), (0..2, 0));
+        assert_eq!(line_at(&app, 0, 1), Some(0));
+        assert_eq!(line_at(&app, 0, 2), Some(1));
+        assert_eq!(line_at(&app, 0, 3), Some(1));
+    }
+
+    #[test]
+    fn wrapped_output_shorter_than_the_pane_fills_it_from_the_top() {
+        let app = app_with_lines(&["a", "bbbbbbbbbbbb"], true);
+        assert_eq!(view(&app), (0..2, 0));
+        assert_eq!(top_scroll_position(&app.runners[0], inner(), false), 2);
+        assert_eq!(line_at(&app, 0, 3), Some(1));
+    }
+}
@@ -1,5 +1,26 @@
 use crate::models::*;
 
-pub fn log_debug(text String) {
-    
+use std::io::Write;
+
+/// Count of debug lines kept in memory, the oldest lines are dropped above it
+const MAX_DEBUG_LINES: usize = 5000;
+
+/// Records a message in the debug log and mirrors it to the debug log file when one is configured
+pub fn log_debug(app: &mut App, level: LogLevel, text: String) {
+    if level < app.debug_record_level {
+        return;
+    }
+    let line = LogLine::new(level, text);
+    if let Some(file) = app.debug_file.as_mut() {
+        if writeln!(file, "{line}").and_then(|_| file.flush()).is_err() {
+            app.debug_file = None;
+            app.notify("Writing the debug log file failed, it is no longer written".into());
+        }
+    }
+    app.debug_lines.push(line);
+    // Dropping in chunks keeps the cost of shifting the lines low
+    if app.debug_lines.len() > MAX_DEBUG_LINES + MAX_DEBUG_LINES / 10 {
+        let excess = app.debug_lines.len() - MAX_DEBUG_LINES;
+        app.debug_lines.drain(..excess);
+    }
 }
@@ -1,18 +1,25 @@
-use crate::{App, EventType, RunnerCfg, RunnerEvent, RunnerState, StdLine};
+use crate::limits;
+use crate::utils::log_debug;
+use crate::{
+    App, EventType, LogLevel, RunSegment, RunTrigger, RunnerCfg, RunnerEvent, RunnerState, StdLine,
+    Stream,
+};
+use chrono::{Local, Utc};
 use crossbeam_channel::{Receiver, Sender};
 use std::{
     io::{self, BufRead},
-    process::{Child, Command, Stdio},
+    os::unix::process::CommandExt,
+    process::{Child, Command, ExitStatus, Stdio},
     sync::{Arc, Mutex},
     thread::{self, JoinHandle},
-    usize,
+    time::Duration,
 };
 
-pub fn start_worker(app: Arc<Mutex<App>>, runner_config: RunnerCfg, runner_index: usize) {
-    start_process(app, runner_config, runner_index)
+pub fn start_worker(app: Arc<Mutex<App>>, runner_config: RunnerCfg, runner_id: usize) {
+    start_process(app, runner_config, runner_id)
 }
 
-fn start_process(app: Arc<Mutex<App>>, runner_config: RunnerCfg, runner_index: usize) {
+fn start_process(app: Arc<Mutex<App>>, mut runner_config: RunnerCfg, runner_id: usize) {
     let command_handle = Arc::new(Mutex::new(None::<Child>));
     let child_join = Arc::new(Mutex::new(None::<JoinHandle<()>>));
     #[allow(unused_assignments)]
@@ -21,71 +28,166 @@ fn start_process(app: Arc<Mutex<App>>, runner_config: RunnerCfg, runner_index: u
     let mut tx = None::<Sender<RunnerEvent>>;
 
     {
-        let process_app = app.lock().unwrap();
-        rx = Some(process_app.runners[runner_index].rx.clone());
-        tx = Some(process_app.runners[runner_index].tx.clone());
+        let mut process_app = app.lock().unwrap();
+        let Some(runner) = process_app.runner_mut(runner_id) else {
+            return;
+        };
+        rx = Some(runner.rx.clone());
+        tx = Some(runner.tx.clone());
     }
+    // Counts the processes that were spawned, so finish events of replaced ones are recognized
+    let mut generation = 0;
     if runner_config.auto_start {
         let mut handle = command_handle.lock().unwrap();
-        let (child, join) = spawn_child(
+        generation += 1;
+        if let Some((child, join)) = spawn_child(
             app.clone(),
             runner_config.clone(),
-            runner_index,
+            runner_id,
             tx.clone().unwrap(),
-        );
-        *handle = Some(child);
-        let mut cjoin = child_join.lock().unwrap();
-        *cjoin = Some(join);
+            RunTrigger::AutoStart,
+            generation,
+        ) {
+            *handle = Some(child);
+            let mut cjoin = child_join.lock().unwrap();
+            *cjoin = Some(join);
+        }
     }
 
+    // Set when the process is killed on purpose, so its exit is not treated as an error
+    let mut stopped = false;
     'process_loop: loop {
         let mut handle = command_handle.lock().unwrap();
         if let Ok(event) = rx.clone().unwrap().recv() {
             match event.event_type {
                 EventType::Stop => {
-                    if handle.is_some() {
-                        let _ = handle.as_mut().unwrap().kill();
+                    if let Some(child) = handle.as_mut() {
+                        stopped = true;
+                        kill_child(&app, &runner_config.name, child);
                     }
                 }
-                EventType::Restart => {
-                    if handle.is_some() {
-                        let _ = handle.as_mut().unwrap().kill();
+                EventType::Restart(trigger) => {
+                    if let Some(child) = handle.as_mut() {
+                        kill_child(&app, &runner_config.name, child);
+                        // The finish event of the killed process is ignored, so its run ends here
+                        let exit_status = child.wait().ok();
+                        let mut process_app = app.lock().unwrap();
+                        if let Some(run) = process_app
+                            .runner_mut(runner_id)
+                            .and_then(|r| r.runs.last_mut())
+                            .filter(|r| r.ended_at.is_none())
+                        {
+                            run.ended_at = Some(Utc::now());
+                            run.exit_status = exit_status;
+                        }
                     }
+                    stopped = false;
 
-                    let (child, join) = spawn_child(
+                    *handle = None;
+                    generation += 1;
+                    if let Some((child, join)) = spawn_child(
                         app.clone(),
                         runner_config.clone(),
-                        runner_index,
+                        runner_id,
                         tx.clone().unwrap(),
-                    );
-                    *handle = Some(child);
-                    let mut cjoin = child_join.lock().unwrap();
-                    *cjoin = Some(join);
+                        trigger,
+                        generation,
+                    ) {
+                        *handle = Some(child);
+                        let mut cjoin = child_join.lock().unwrap();
+                        *cjoin = Some(join);
+                    }
                 }
-                EventType::Finish => {
-                    let mut join = child_join.lock().unwrap();
-                    if let Some(ref inner_join) = *join {
-                        if inner_join.is_finished() {
-                            let mut process_app = app.lock().unwrap();
-                            let jinner_join = join.take().unwrap();
-                            let res = jinner_join.join().is_ok();
-                            if res {
-                                process_app.runners[runner_index].state = RunnerState::Finish;
-                            } else {
-                                process_app.runners[runner_index].state = RunnerState::Error;
+                EventType::Finish(finished) => {
+                    // Finish events of processes that were already replaced by a restart are ignored
+                    if finished != generation {
+                        continue;
+                    }
+                    let Some(join) = child_join.lock().unwrap().take() else {
+                        continue;
+                    };
+                    // The reader thread sends the event as its last step, so this doesn't block
+                    let res = join.join().is_ok();
+                    let exit_status = handle.as_mut().and_then(wait_for_exit);
+
+                    {
+                        let mut process_app = app.lock().unwrap();
+                        if !res {
+                            log_debug(
+                                &mut process_app,
+                                LogLevel::Error,
+                                format!("{}: output reader panicked", runner_config.name),
+                            );
+                        }
+                        let Some(runner) = process_app.runner_mut(runner_id) else {
+                            break 'process_loop;
+                        };
+                        runner.pid = None;
+                        runner.exit_status = exit_status;
+                        if let Some(run) = runner.runs.last_mut() {
+                            run.ended_at = Some(Utc::now());
+                            run.exit_status = exit_status;
+                        }
+                        let succeeded = stopped || exit_status.is_none_or(|s| s.success());
+                        runner.state = if res && succeeded {
+                            RunnerState::Finish
+                        } else {
+                            RunnerState::Error
+                        };
+                        let message = match exit_status {
+                            Some(status) => {
+                                format!("{} exited with {}", runner.name, format_exit(&status))
                             }
+                            None => format!("{} finished", runner.name),
+                        };
+                        let level = match runner.state {
+                            RunnerState::Error => LogLevel::Warn,
+                            _ => LogLevel::Info,
+                        };
+                        let state = runner.state.label();
+                        log_debug(&mut process_app, level, format!("{message}, now {state}"));
+                        if !stopped {
+                            process_app.notify(message);
                         }
                     }
 
-                    if runner_config.restart_on_finish {
+                    if runner_config.restart_on_finish && !stopped {
+                        let trigger = if exit_status.is_none_or(|s| s.success()) {
+                            RunTrigger::Finish
+                        } else {
+                            RunTrigger::Crash
+                        };
+                        log_debug(
+                            &mut app.lock().unwrap(),
+                            LogLevel::Info,
+                            format!(
+                                "{}: restarting after {}",
+                                runner_config.name,
+                                trigger.label()
+                            ),
+                        );
+                        // The receiver lives as long as this loop, so sending can't fail
                         let _ = tx.clone().unwrap().send(RunnerEvent {
-                            event_type: EventType::Restart,
+                            event_type: EventType::Restart(trigger),
+                        });
+                    }
+                }
+                EventType::Reload(new_config) => {
+                    runner_config = *new_config;
+                    let is_running = handle
+                        .as_mut()
+                        .is_some_and(|child| matches!(child.try_wait(), Ok(None)));
+                    // A runner that was stopped by hand stays stopped with the new config
+                    if is_running || (runner_config.auto_start && !stopped) {
+                        // The receiver lives as long as this loop, so sending can't fail
+                        let _ = tx.clone().unwrap().send(RunnerEvent {
+                            event_type: EventType::Restart(RunTrigger::Reload),
                         });
                     }
                 }
                 EventType::ApplicationQuit => {
-                    if handle.is_some() {
-                        let _ = handle.as_mut().unwrap().kill();
+                    if let Some(child) = handle.as_mut() {
+                        kill_child(&app, &runner_config.name, child);
                     }
                     break 'process_loop;
                 }
@@ -94,12 +196,110 @@ fn start_process(app: Arc<Mutex<App>>, runner_config: RunnerCfg, runner_index: u
     }
 }
 
+/// Stops the process together with everything it started, first with SIGTERM and with SIGKILL
+/// when the process is still running after a grace period. A failure is only logged as the
+/// processes have usually exited already
+fn kill_child(app: &Arc<Mutex<App>>, name: &str, child: &mut Child) {
+    let Some(exited) = exit_state(child) else {
+        // A reaped process no longer holds on to its group id, which could be reused by now
+        return;
+    };
+    if exited {
+        // Processes it started in the background can still hold on to the output or a port
+        let _ = signal_group(child, libc::SIGKILL);
+        return;
+    }
+    let result = signal_group(child, libc::SIGTERM);
+    for _ in 0..KILL_GRACE_ATTEMPTS {
+        if exit_state(child) != Some(false) {
+            break;
+        }
+        thread::sleep(EXIT_WAIT_INTERVAL);
+    }
+    let killed = exit_state(child) == Some(false);
+    // The process isn't reaped yet, so the group is still its own. Processes it started in the
+    // background can still hold on to the output or a port after it exited
+    let _ = signal_group(child, libc::SIGKILL);
+
+    let mut app = app.lock().unwrap();
+    match result {
+        Ok(()) if killed => log_debug(
+            &mut app,
+            LogLevel::Warn,
+            format!("{name}: killed pid {}, it ignored SIGTERM", child.id()),
+        ),
+        Ok(()) => log_debug(
+            &mut app,
+            LogLevel::Info,
+            format!("{name}: stopped pid {}", child.id()),
+        ),
+        Err(err) => log_debug(
+            &mut app,
+            LogLevel::Debug,
+            format!("{name}: stopping pid {} failed: {err}", child.id()),
+        ),
+    }
+}
+
+/// Whether the process exited, without reaping it so its group id stays reserved. None when it
+/// was reaped already
+fn exit_state(child: &Child) -> Option<bool> {
+    // SAFETY: siginfo_t is plain data, and waitid only writes to it
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let options = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+    // SAFETY: waitid only queries the state of the child, WNOWAIT leaves it waitable
+    let result = unsafe { libc::waitid(libc::P_PID, child.id(), &mut info, options) };
+    // An error means the process was reaped already
+    if result != 0 {
+        return None;
+    }
+    // SAFETY: waitid succeeded, so the pid field is set, to zero when the process still runs
+    Some(unsafe { info.si_pid() } != 0)
+}
+
+/// Sends the signal to the process group the child leads
+fn signal_group(child: &Child, signal: libc::c_int) -> io::Result<()> {
+    // SAFETY: killpg only sends a signal, the group is the one the child was started in
+    if unsafe { libc::killpg(child.id() as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Waits a short while for a process whose output has ended to exit
+fn wait_for_exit(child: &mut Child) -> Option<ExitStatus> {
+    for _ in 0..EXIT_WAIT_ATTEMPTS {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) => thread::sleep(EXIT_WAIT_INTERVAL),
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+const EXIT_WAIT_ATTEMPTS: usize = 20;
+const EXIT_WAIT_INTERVAL: Duration = Duration::from_millis(50);
+/// Count of exit checks after SIGTERM before the process is killed, two seconds in total
+const KILL_GRACE_ATTEMPTS: usize = 40;
+
+/// Formats an exit status as its code, or as the signal that killed the process
+pub fn format_exit(status: &ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("code {code}"),
+        None => status.to_string(),
+    }
+}
+
 fn spawn_child(
     app: Arc<Mutex<App>>,
     runner_config: RunnerCfg,
-    runner_index: usize,
+    runner_id: usize,
     tx: Sender<RunnerEvent>,
-) -> (Child, JoinHandle<()>) {
+    trigger: RunTrigger,
+    generation: usize,
+) -> Option<(Child, JoinHandle<()>)> {
     let lead = runner_config.args[0].to_owned();
     let args: Vec<&String> = runner_config.args.iter().skip(1).collect();
 
@@ -107,14 +307,28 @@ fn spawn_child(
     for arg in args {
         cmd = cmd.arg(arg);
     }
-    if runner_config.dir != "" {
+    if !runner_config.dir.is_empty() {
         cmd = cmd.current_dir(runner_config.dir)
     }
-    let mut child = cmd
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .expect("Failed to start ping process");
+    cmd = cmd.envs(&runner_config.env);
+    // A group of its own lets the process be killed together with its children, e.g. the
+    // commands a `sh -c` runner starts
+    cmd = cmd.process_group(0);
+    limits::apply(cmd, &runner_config.limits);
+    let spawned = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(err) => {
+            let mut process_app = app.lock().unwrap();
+            let runner = process_app.runner_mut(runner_id)?;
+            runner.state = RunnerState::Error;
+            runner.pid = None;
+            let message = format!("{} failed to start: {err}", runner.name);
+            log_debug(&mut process_app, LogLevel::Error, message.clone());
+            process_app.notify(message);
+            return None;
+        }
+    };
 
     let stdout = child.stdout.take().expect("Failed to capture stdout");
     let stdout_reader = io::BufReader::new(stdout);
@@ -123,54 +337,126 @@ fn spawn_child(
     let stderr_reader = io::BufReader::new(stderr);
 
     let reader_app = app.clone();
+    let reader_name = runner_config.name.clone();
     let join = thread::spawn(move || {
         let out_reader = reader_app.clone();
-        let stdout_join = thread::spawn(move || {
-            // read the lines 1 by 1
-            for line in stdout_reader.lines() {
-                let mut process_app = out_reader.lock().unwrap();
-                let runner = &mut process_app.runners.get_mut(runner_index).unwrap();
-
-                let s = line.expect("Could not get a line");
-                runner.lines.push(StdLine::new(s));
-                if runner.vertical_scroll_position == runner.vertical_scroll_size {
-                    runner.vertical_scroll_position =
-                        runner.vertical_scroll_position.saturating_add(1);
-                }
-                runner.vertical_scroll_size = runner.vertical_scroll_size.saturating_add(1);
-            }
-        });
+        let stdout_join =
+            thread::spawn(move || read_lines(out_reader, runner_id, stdout_reader, Stream::Stdout));
 
         let err_reader = reader_app.clone();
-        let stderr_join = thread::spawn(move || {
-            // read the lines 1 by 1
-            for line in stderr_reader.lines() {
-                let mut process_app = err_reader.lock().unwrap();
-                let runner = &mut process_app.runners.get_mut(runner_index).unwrap();
-
-                let s = line.expect("Could not get a line");
-                runner.lines.push(StdLine::new(s));
-                if runner.vertical_scroll_position == runner.vertical_scroll_size {
-                    runner.vertical_scroll_position =
-                        runner.vertical_scroll_position.saturating_add(1);
-                }
-                runner.vertical_scroll_size = runner.vertical_scroll_size.saturating_add(1);
-            }
-        });
+        let stderr_join =
+            thread::spawn(move || read_lines(err_reader, runner_id, stderr_reader, Stream::Stderr));
 
-        let _ = stdout_join.join();
-        let _ = stderr_join.join();
+        for join in [stdout_join, stderr_join] {
+            if join.join().is_err() {
+                let mut process_app = reader_app.lock().unwrap();
+                log_debug(
+                    &mut process_app,
+                    LogLevel::Error,
+                    format!("{reader_name}: output reader panicked"),
+                );
+            }
+        }
+        // The worker may have quit already, in which case nobody waits for the event
         let _ = tx.send(RunnerEvent {
-            event_type: EventType::Finish,
+            event_type: EventType::Finish(generation),
         });
     });
 
     {
         let mut process_app = app.lock().unwrap();
 
-        process_app.runners[runner_index].state = RunnerState::Active;
-        process_app.runners[runner_index].should_restart = false;
+        // A runner that was removed by a reload in the meantime has nothing left to start
+        let Some(runner) = process_app.runner_mut(runner_id) else {
+            let _ = signal_group(&child, libc::SIGKILL);
+            let _ = child.wait();
+            return None;
+        };
+        if runner.started_at.is_some() {
+            runner.restart_count += 1;
+        }
+        let first_line = runner.lines.len();
+        if let Some(previous) = runner.runs.last() {
+            let ended = match previous.exit_status {
+                Some(status) => format!("previous run exited with {}", format_exit(&status)),
+                None => "previous run ended".into(),
+            };
+            let separator = format!(
+                "──── run {} · {} · {} · {ended} ────",
+                runner.runs.len() + 1,
+                trigger.label(),
+                Local::now().format("%H:%M:%S"),
+            );
+            runner.lines.push(StdLine::new(separator, Stream::Marker));
+            runner.vertical_scroll_size = runner.vertical_scroll_size.saturating_add(1);
+            if runner.follow {
+                runner.vertical_scroll_position = runner.vertical_scroll_size;
+            }
+        }
+        runner.runs.push(RunSegment {
+            started_at: Utc::now(),
+            ended_at: None,
+            exit_status: None,
+            trigger,
+            first_line,
+        });
+        runner.state = RunnerState::Active;
+        runner.started_at = Some(Utc::now());
+        runner.pid = Some(child.id());
+        runner.exit_status = None;
+        runner.should_restart = false;
+
+        let message = format!(
+            "{}: started pid {} ({}) in `{}`",
+            runner.name,
+            child.id(),
+            trigger.label(),
+            runner_config.args.join(" ")
+        );
+        log_debug(&mut process_app, LogLevel::Info, message);
     }
 
-    (child, join)
+    Some((child, join))
+}
+
+/// Appends the lines of one output stream of the process to the runner until the stream ends
+fn read_lines(app: Arc<Mutex<App>>, runner_id: usize, reader: impl BufRead, stream: Stream) {
+    for line in reader.lines() {
+        let mut process_app = app.lock().unwrap();
+        let content = match line {
+            Ok(content) => content,
+            // Lines that aren't valid UTF-8 are skipped, the reader continues after them
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                let name = process_app
+                    .runner_mut(runner_id)
+                    .map_or(String::new(), |r| r.name.clone());
+                log_debug(
+                    &mut process_app,
+                    LogLevel::Warn,
+                    format!("{name}: skipped a line of {stream:?}: {err}"),
+                );
+                continue;
+            }
+            Err(err) => {
+                let name = process_app
+                    .runner_mut(runner_id)
+                    .map_or(String::new(), |r| r.name.clone());
+                log_debug(
+                    &mut process_app,
+                    LogLevel::Error,
+                    format!("{name}: reading {stream:?} failed: {err}"),
+                );
+                break;
+            }
+        };
+        // Output of a runner that was removed by a reload is dropped
+        let Some(runner) = process_app.runner_mut(runner_id) else {
+            break;
+        };
+        runner.lines.push(StdLine::new(content, stream));
+        runner.vertical_scroll_size = runner.vertical_scroll_size.saturating_add(1);
+        if runner.follow {
+            runner.vertical_scroll_position = runner.vertical_scroll_size;
+        }
+    }
 }